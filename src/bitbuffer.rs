@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, Result};
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct BitBuffer {
     pub data: Vec<u8>,
@@ -7,13 +9,23 @@ pub struct BitBuffer {
     pub num_bits: usize,
 }
 
+impl Default for BitBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BitBuffer {
-    pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&self)?)
     }
 
-    pub fn deserialize(input: &[u8]) -> Self {
-        bincode::deserialize(input).unwrap()
+    pub fn deserialize(input: &[u8]) -> Result<Self> {
+        let bitbuffer: Self = bincode::deserialize(input)?;
+        if bitbuffer.num_bits > bitbuffer.data.len() * 8 {
+            return Err(Error::Corrupt(format!("bit buffer claims {} bits but holds {} bytes", bitbuffer.num_bits, bitbuffer.data.len())));
+        }
+        Ok(bitbuffer)
     }
 
     pub fn new() -> Self {
@@ -25,7 +37,7 @@ impl BitBuffer {
     }
 
     pub fn write_bit(&mut self, bit: bool) {
-        if self.num_bits % 8 == 0 {
+        if self.num_bits.is_multiple_of(8) {
             self.data.push(0);
         }
         if bit {
@@ -50,31 +62,22 @@ impl BitBuffer {
         if self.read_pos >= self.num_bits {
             return None;
         }
-        let bit = self.data[self.read_pos / 8] & (1 << (self.read_pos % 8)) != 0;
+        let bit = self.data.get(self.read_pos / 8)? & (1 << (self.read_pos % 8)) != 0;
         self.read_pos += 1;
         Some(bit)
     }
 
     pub fn read_byte(&mut self) -> Option<u8> {
-        if self.read_pos + 8 > self.data.len() * 8 {
-            return None;
-        }
-        let mut byte = 0;
-        for i in 0..8 {
-            if self.read_bit().unwrap() {
-                byte |= 1 << i;
-            }
-        }
-        Some(byte)
+        self.read_bits(8).map(|byte| byte as u8)
     }
 
     pub fn read_bits(&mut self, num_bits: u8) -> Option<u32> {
-        if self.read_pos + num_bits as usize > self.num_bits {
+        if self.read_pos + num_bits as usize > self.num_bits || num_bits > 32 {
             return None;
         }
         let mut bits = 0;
         for i in 0..num_bits {
-            if self.read_bit().unwrap() {
+            if self.read_bit()? {
                 bits |= 1 << i;
             }
         }
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use glob::{Pattern, PatternError};
+use huffman::{compress::DEFAULT_BLOCK_SIZE, CompressOptions, OverwritePolicy};
+
+#[derive(Parser, Debug)]
+#[command(name = "huffman", version, about = "Compress directories into .tmy archives")]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Compress stdin to stdout
+    #[arg(short = 'c', long = "stdout", conflicts_with = "decompress")]
+    pub compress: bool,
+    /// Decompress stdin to stdout
+    #[arg(short = 'd', long = "decompress")]
+    pub decompress: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compress files and directories into a .tmy archive
+    #[command(visible_alias = "c")]
+    Compress {
+        /// Files and directories to compress
+        #[arg(default_value = ".")]
+        inputs: Vec<PathBuf>,
+        /// Archive to write (defaults to <name>.tmy in the current directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+        /// Split the archive into volumes of at most this size, e.g. 700M (<output>.001, <output>.002, ...)
+        #[arg(long, value_parser = parse_size)]
+        volume_size: Option<u64>,
+        /// Encrypt file data with a password, asked for or taken from HUFFMAN_PASSWORD
+        #[arg(long)]
+        encrypt: bool,
+        /// Also encrypt names and the directory structure (implies --encrypt)
+        #[arg(long)]
+        encrypt_names: bool,
+    },
+    /// Extract a .tmy archive
+    #[command(visible_alias = "x")]
+    Extract {
+        /// Archive to extract
+        archive: PathBuf,
+        /// Only extract entries matching these paths or glob patterns
+        #[arg(value_parser = parse_pattern)]
+        patterns: Vec<Pattern>,
+        /// Directory to extract into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// What to do with entries that already exist: skip, overwrite, keep-newer, rename or fail
+        #[arg(long, default_value = "skip")]
+        overwrite: OverwritePolicy,
+        /// Restore file owners and groups (usually requires root)
+        #[arg(long)]
+        same_owner: bool,
+    },
+    /// Add files and directories to an existing .tmy archive
+    #[command(visible_alias = "a")]
+    Add {
+        /// Archive to add to
+        archive: PathBuf,
+        /// Files and directories to add
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Replace entries of an existing .tmy archive, recompressing only changed files
+    #[command(visible_alias = "u")]
+    Update {
+        /// Archive to update
+        archive: PathBuf,
+        /// Files and directories to add or replace
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Remove entries from an existing .tmy archive
+    Delete {
+        /// Archive to remove entries from
+        archive: PathBuf,
+        /// Paths or glob patterns of the entries to remove
+        #[arg(required = true, value_parser = parse_pattern)]
+        patterns: Vec<Pattern>,
+    },
+    /// List the contents of a .tmy archive
+    #[command(visible_alias = "l")]
+    List {
+        /// Archive to list
+        archive: PathBuf,
+    },
+    /// Check that every entry of a .tmy archive decodes
+    #[command(visible_alias = "t")]
+    Test {
+        /// Archive to test
+        archive: PathBuf,
+    },
+}
+
+/// How files and directories are picked up and compressed when archiving.
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    /// Archive the targets of symbolic links instead of the links themselves
+    #[arg(short = 'L', long)]
+    follow_symlinks: bool,
+    /// Leave out entries matching this gitignore-style pattern, relative to each input (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Only archive files matching this gitignore-style pattern, relative to each input (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Read exclude patterns from FILE, one per line (repeatable)
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+    /// Also leave out what the .gitignore files inside the inputs exclude
+    #[arg(long)]
+    git_ignore: bool,
+    /// Record access times, which makes the archive change whenever the inputs are read
+    #[arg(long)]
+    access_times: bool,
+    /// Compress small files together in blocks, which finds repeats between files
+    #[arg(long)]
+    solid: bool,
+    /// Size of the blocks used by --solid, e.g. 4M [default: 1M]
+    #[arg(long, requires = "solid", value_parser = parse_size)]
+    block_size: Option<u64>,
+}
+
+impl From<InputArgs> for CompressOptions {
+    fn from(input: InputArgs) -> Self {
+        CompressOptions {
+            follow_symlinks: input.follow_symlinks,
+            exclude: input.exclude,
+            include: input.include,
+            ignore_files: input.exclude_from,
+            git_ignore: input.git_ignore,
+            access_times: input.access_times,
+            solid_block_size: input.solid.then(|| input.block_size.unwrap_or(DEFAULT_BLOCK_SIZE)),
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<u64, String> {
+    let (digits, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => size.split_at(index),
+        None => (size, ""),
+    };
+    let multiplier = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return Err(format!("unknown size unit '{}', expected K, M or G", unit)),
+    };
+    match digits.parse::<u64>().ok().and_then(|digits| digits.checked_mul(multiplier)) {
+        Some(0) => Err("size must be greater than zero".to_string()),
+        Some(size) => Ok(size),
+        None => Err(format!("invalid size '{}'", size)),
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern, PatternError> {
+    Pattern::new(pattern.trim_end_matches('/'))
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+/// Block size used when solid compression is asked for without one.
+pub const DEFAULT_BLOCK_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    /// Archive the targets of symbolic links instead of the links themselves.
+    pub follow_symlinks: bool,
+    /// Gitignore style patterns of entries to leave out, relative to each input.
+    pub exclude: Vec<String>,
+    /// If not empty, only files matching one of these patterns are archived.
+    pub include: Vec<String>,
+    /// Files with more exclude patterns, one per line.
+    pub ignore_files: Vec<PathBuf>,
+    /// Also leave out what the `.gitignore` files inside the inputs exclude.
+    pub git_ignore: bool,
+    /// Record access times. They change whenever the tree is read, so an archive that
+    /// records them is not reproducible; otherwise the modification time is stored instead.
+    pub access_times: bool,
+    /// Compress files smaller than this together in blocks of up to this many bytes,
+    /// instead of each on its own.
+    pub solid_block_size: Option<u64>,
+}
@@ -0,0 +1,117 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{aead::{Aead, KeyInit, Payload}, ChaCha20Poly1305, Key, Nonce};
+
+use crate::error::{Error, Result};
+
+/// Passphrase and settings of an encrypted archive.
+#[derive(Debug, Clone)]
+pub struct Encryption {
+    pub passphrase: String,
+    /// Also encrypt the index, which hides names and the directory structure.
+    pub encrypt_index: bool,
+}
+
+const SALT_LEN: usize = 16;
+pub(crate) const TAG_LEN: usize = 16;
+/// Costs above which key parameters read from an archive are refused, so a crafted
+/// archive cannot make opening it take unbounded memory or time: memory in KiB,
+/// number of passes and degree of parallelism.
+const MAX_M_COST: u32 = 1 << 20;
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 64;
+
+/// What a message is encrypted for. Nonces are derived from it and the offset of the
+/// message, so no two messages of one archive share a nonce.
+#[derive(Clone, Copy)]
+pub(crate) enum Purpose {
+    Check = 0,
+    Payload = 1,
+    Index = 2,
+}
+
+/// ChaCha20-Poly1305 with a key derived from a passphrase by Argon2id. An encrypted
+/// archive starts its data with a key block: the salt, the Argon2 memory, time and
+/// parallelism costs, and an empty message encrypted with the key to check passphrases.
+pub(crate) struct Cipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Cipher {
+    pub(crate) const KEY_BLOCK_LEN: usize = SALT_LEN + 12 + TAG_LEN;
+
+    /// Derives a key from `passphrase` with a new random salt and returns it with its key block.
+    pub(crate) fn create(passphrase: &str) -> Result<(Self, Vec<u8>)> {
+        let mut salt = [0; SALT_LEN];
+        getrandom::fill(&mut salt).map_err(|error| Error::Io(std::io::Error::other(error.to_string())))?;
+        let params = Params::DEFAULT;
+        let costs = [params.m_cost(), params.t_cost(), params.p_cost()];
+        let cipher = Self::derive(passphrase, &salt, costs)?;
+
+        let mut block = salt.to_vec();
+        costs.iter().for_each(|cost| block.extend_from_slice(&cost.to_le_bytes()));
+        block.extend(cipher.encrypt(Purpose::Check, 0, &[])?);
+        Ok((cipher, block))
+    }
+
+    /// Derives the key described by `block`, failing if `passphrase` is not the one it was made with.
+    pub(crate) fn open(passphrase: &str, block: &[u8]) -> Result<Self> {
+        if block.len() != Self::KEY_BLOCK_LEN {
+            return Err(Error::Corrupt("key block is truncated".to_string()));
+        }
+        let (salt, rest) = block.split_at(SALT_LEN);
+        let cost = |index: usize| u32::from_le_bytes([rest[4 * index], rest[4 * index + 1], rest[4 * index + 2], rest[4 * index + 3]]);
+        let costs = [cost(0), cost(1), cost(2)];
+        if costs[0] > MAX_M_COST {
+            return Err(Error::Corrupt(format!("key derivation needs {} KiB of memory, more than allowed", costs[0])));
+        }
+        if costs[1] > MAX_T_COST {
+            return Err(Error::Corrupt(format!("key derivation needs {} passes, more than allowed", costs[1])));
+        }
+        if costs[2] > MAX_P_COST {
+            return Err(Error::Corrupt(format!("key derivation needs {} lanes, more than allowed", costs[2])));
+        }
+        let cipher = Self::derive(passphrase, salt, costs)?;
+        cipher.decrypt(Purpose::Check, 0, &rest[12..]).map_err(|_| Error::WrongPassword)?;
+        Ok(cipher)
+    }
+
+    fn derive(passphrase: &str, salt: &[u8], [m_cost, t_cost, p_cost]: [u32; 3]) -> Result<Self> {
+        let invalid = |error: argon2::Error| Error::Corrupt(format!("invalid key derivation parameters: {}", error));
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32)).map_err(invalid)?;
+        let mut key = [0; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(invalid)?;
+        Ok(Cipher { cipher: ChaCha20Poly1305::new(&Key::from(key)) })
+    }
+
+    fn nonce(purpose: Purpose, offset: u64) -> Nonce {
+        let mut nonce = [0; 12];
+        nonce[..4].copy_from_slice(&(purpose as u32).to_le_bytes());
+        nonce[4..].copy_from_slice(&offset.to_le_bytes());
+        Nonce::from(nonce)
+    }
+
+    pub(crate) fn encrypt(&self, purpose: Purpose, offset: u64, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.encrypt(&Self::nonce(purpose, offset), plaintext)
+            .map_err(|_| Error::InvalidParameter("data is too large to encrypt".to_string()))
+    }
+
+    /// Fails if `ciphertext` was modified, or was not encrypted with this key for `purpose` at `offset`.
+    pub(crate) fn decrypt(&self, purpose: Purpose, offset: u64, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.decrypt(&Self::nonce(purpose, offset), ciphertext)
+            .map_err(|_| Error::Corrupt("encrypted data failed authentication, the archive was modified".to_string()))
+    }
+
+    /// A tag proving `data` is unmodified without encrypting it, for an index left readable.
+    pub(crate) fn authenticate(&self, purpose: Purpose, offset: u64, data: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.encrypt(&Self::nonce(purpose, offset), Payload { msg: &[], aad: data })
+            .map_err(|_| Error::InvalidParameter("data is too large to authenticate".to_string()))
+    }
+
+    pub(crate) fn verify(&self, purpose: Purpose, offset: u64, data: &[u8], tag: &[u8]) -> Result<()> {
+        self.cipher.decrypt(&Self::nonce(purpose, offset), Payload { msg: tag, aad: data })
+            .map(|_| ())
+            .map_err(|_| Error::Corrupt("index failed authentication, the archive was modified".to_string()))
+    }
+}
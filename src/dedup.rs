@@ -0,0 +1,48 @@
+use std::{collections::HashMap, fs, sync::Mutex};
+
+use crate::name::Name;
+
+/// Remembers the first archive path seen for every hard linked inode and for every
+/// distinct file content, so later copies can be stored as references to it.
+#[derive(Default)]
+pub(crate) struct Dedup {
+    inodes: Mutex<HashMap<(u64, u64), Name>>,
+    contents: Mutex<HashMap<[u8; 32], Name>>,
+}
+
+impl Dedup {
+    /// Returns the path already holding the inode behind `metadata`, or records `path` as its holder.
+    #[cfg(unix)]
+    pub(crate) fn claim_inode(&self, metadata: &fs::Metadata, path: &Name) -> Option<Name> {
+        use std::os::unix::fs::MetadataExt;
+
+        if metadata.nlink() < 2 {
+            return None;
+        }
+        let mut inodes = self.inodes.lock().unwrap();
+        match inodes.get(&(metadata.dev(), metadata.ino())) {
+            Some(original) => Some(original.clone()),
+            None => {
+                inodes.insert((metadata.dev(), metadata.ino()), path.clone());
+                None
+            },
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn claim_inode(&self, _: &fs::Metadata, _: &Name) -> Option<Name> {
+        None
+    }
+
+    /// Returns the path already holding the content with BLAKE3 hash `hash`, or records `path` as its holder.
+    pub(crate) fn claim_content(&self, hash: [u8; 32], path: &Name) -> Option<Name> {
+        let mut contents = self.contents.lock().unwrap();
+        match contents.get(&hash) {
+            Some(original) => Some(original.clone()),
+            None => {
+                contents.insert(hash, path.clone());
+                None
+            },
+        }
+    }
+}
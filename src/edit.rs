@@ -0,0 +1,57 @@
+use std::{fs, io::BufWriter, path::Path};
+
+use glob::Pattern;
+
+use crate::{compress::CompressOptions, error::{Error, PathContext, Result}, file_system::Archive, name::Name, progress::Progress, reader::ArchiveReader, volumes, writer::ArchiveWriter};
+
+/// Writes a new version of `archive` next to it and replaces the original only
+/// once the new one is complete.
+/// The new version is encrypted like the original, which needs `passphrase`.
+fn rewrite(archive: &Path, passphrase: Option<&str>, edit: impl FnOnce(&ArchiveReader, &ArchiveWriter) -> Result<Archive>) -> Result<()> {
+    if volumes::split_base(archive).is_some() {
+        return Err(Error::InvalidParameter(format!("{} is split into volumes, which cannot be modified", archive.display())));
+    }
+    // Keeping the .tmy extension stops the archiver from picking up its own output.
+    let temporary = archive.with_extension("tmp.tmy");
+    let result = (|| {
+        let reader = ArchiveReader::open(archive, passphrase)?;
+        let encryption = reader.encryption()?;
+        let file = fs::File::create(&temporary).at(&temporary)?;
+        let writer = ArchiveWriter::new(Box::new(BufWriter::new(file)), encryption.as_ref())?;
+        let index = edit(&reader, &writer)?;
+        writer.finish(&index)
+    })();
+    match result {
+        Ok(()) => fs::rename(&temporary, archive).at(archive),
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            Err(error)
+        },
+    }
+}
+
+/// Adds `paths` to the root of `archive`. Fails if an entry with the same name exists.
+pub fn add<P: AsRef<Path> + Sync>(archive: &Path, paths: &[P], options: &CompressOptions, passphrase: Option<&str>, progress: &dyn Progress) -> Result<()> {
+    rewrite(archive, passphrase, |reader, writer| Archive::merge_paths(reader, paths, options, writer, progress, false))
+}
+
+/// Adds `paths` to the root of `archive`, replacing entries with the same name.
+/// Only files whose size or modification time changed are compressed again.
+pub fn update<P: AsRef<Path> + Sync>(archive: &Path, paths: &[P], options: &CompressOptions, passphrase: Option<&str>, progress: &dyn Progress) -> Result<()> {
+    rewrite(archive, passphrase, |reader, writer| Archive::merge_paths(reader, paths, options, writer, progress, true))
+}
+
+/// Removes every entry matching `patterns` from `archive` and returns their paths.
+/// The files left in a solid block that lost one are compressed again, without its data.
+pub fn delete(archive: &Path, patterns: &[Pattern], passphrase: Option<&str>, progress: &dyn Progress) -> Result<Vec<Name>> {
+    let mut removed = Vec::new();
+    rewrite(archive, passphrase, |reader, writer| {
+        let (index, matched) = Archive::remove_matching(reader, patterns, writer, progress)?;
+        if matched.is_empty() {
+            return Err(Error::InvalidParameter("no entries match the given patterns".to_string()));
+        }
+        removed = matched;
+        Ok(index)
+    })?;
+    Ok(removed)
+}
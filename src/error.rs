@@ -0,0 +1,67 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+use crate::header::Header;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Corrupt(String),
+    ChecksumMismatch(String),
+    /// The header starting with `magic` has a format `version` newer than this build.
+    UnsupportedVersion { magic: [u8; 4], version: u16 },
+    InvalidParameter(String),
+    MissingVolume(PathBuf),
+    PasswordRequired,
+    WrongPassword,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Corrupt(message) => write!(f, "corrupt data: {}", message),
+            Error::ChecksumMismatch(path) => write!(f, "checksum mismatch in {}", path),
+            Error::UnsupportedVersion { magic, version } => write!(f, "{} from newer version: format {} is not supported, this build reads up to {}", Header::kind(*magic), version, Header::current_version(*magic)),
+            Error::InvalidParameter(message) => write!(f, "invalid parameter: {}", message),
+            Error::MissingVolume(path) => write!(f, "missing volume {}", path.display()),
+            Error::PasswordRequired => write!(f, "archive is encrypted, a password is required"),
+            Error::WrongPassword => write!(f, "wrong password"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(error: bincode::Error) -> Self {
+        match *error {
+            bincode::ErrorKind::Io(error) if error.kind() != io::ErrorKind::UnexpectedEof => Error::Io(error),
+            error => Error::Corrupt(error.to_string()),
+        }
+    }
+}
+
+pub trait PathContext<T> {
+    fn at(self, path: &Path) -> Result<T>;
+}
+
+impl<T> PathContext<T> for io::Result<T> {
+    fn at(self, path: &Path) -> Result<T> {
+        self.map_err(|error| Error::Io(io::Error::new(error.kind(), format!("{}: {}", path.display(), error))))
+    }
+}
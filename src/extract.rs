@@ -0,0 +1,125 @@
+use std::{fs, io, path::{Path, PathBuf}, str::FromStr, time::SystemTime};
+
+use crate::{error::{Error, PathContext, Result}, name::Name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    #[default]
+    Skip,
+    Overwrite,
+    KeepNewer,
+    Rename,
+    Fail,
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> std::result::Result<Self, Self::Err> {
+        match policy {
+            "skip" => Ok(OverwritePolicy::Skip),
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "keep-newer" => Ok(OverwritePolicy::KeepNewer),
+            "rename" => Ok(OverwritePolicy::Rename),
+            "fail" => Ok(OverwritePolicy::Fail),
+            _ => Err(format!("unknown overwrite policy '{}', expected skip, overwrite, keep-newer, rename or fail", policy)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    pub overwrite: OverwritePolicy,
+    /// Restore the owner and group of extracted entries, which usually needs root.
+    pub restore_ownership: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractReport {
+    pub skipped: Vec<PathBuf>,
+    pub replaced: Vec<PathBuf>,
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    /// Entries that were not written because their name is unsafe, with the reason.
+    pub refused: Vec<(Name, String)>,
+}
+
+impl ExtractReport {
+    pub fn merge(mut self, other: ExtractReport) -> Self {
+        self.skipped.extend(other.skipped);
+        self.replaced.extend(other.replaced);
+        self.renamed.extend(other.renamed);
+        self.refused.extend(other.refused);
+        self
+    }
+}
+
+/// Returns why `name` must not be used as a single path component below the
+/// output directory, or `None` if it is safe.
+pub(crate) fn unsafe_name(name: &Name) -> Option<&'static str> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        Some("empty name")
+    } else if bytes == b"." || bytes == b".." {
+        Some("name refers to a directory outside the entry")
+    } else if bytes.contains(&b'/') || (cfg!(windows) && bytes.iter().any(|&byte| byte == b'\\' || byte == b':')) {
+        Some("name contains a path separator")
+    } else if bytes.contains(&0) {
+        Some("name contains a NUL byte")
+    } else {
+        None
+    }
+}
+
+fn free_name(target: &Path) -> PathBuf {
+    let mut suffix = 1;
+    loop {
+        let mut name = target.as_os_str().to_owned();
+        name.push(format!(".{}", suffix));
+        if fs::symlink_metadata(&name).is_err() {
+            return PathBuf::from(name);
+        }
+        suffix += 1;
+    }
+}
+
+fn remove(target: &Path, existing: &fs::Metadata) -> Result<()> {
+    match existing.is_dir() {
+        true => fs::remove_dir_all(target).at(target),
+        false => fs::remove_file(target).at(target),
+    }
+}
+
+/// Decides what happens when `target` already exists. Returns the path the entry
+/// should be written to, or `None` if the entry is skipped.
+pub(crate) fn resolve_conflict(target: PathBuf, is_directory: bool, modified: Option<SystemTime>, options: &ExtractOptions, report: &mut ExtractReport) -> Result<Option<PathBuf>> {
+    let Ok(existing) = fs::symlink_metadata(&target) else {
+        return Ok(Some(target));
+    };
+    if is_directory && existing.is_dir() {
+        return Ok(Some(target));
+    }
+
+    let replace = match options.overwrite {
+        OverwritePolicy::Skip => false,
+        OverwritePolicy::Overwrite => true,
+        OverwritePolicy::KeepNewer => match (existing.modified(), modified) {
+            (Ok(existing), Some(archived)) => existing < archived,
+            _ => false,
+        },
+        OverwritePolicy::Rename => {
+            let renamed = free_name(&target);
+            report.renamed.push((target, renamed.clone()));
+            return Ok(Some(renamed));
+        },
+        OverwritePolicy::Fail => return Err(Error::Io(io::Error::new(io::ErrorKind::AlreadyExists, format!("{}: already exists", target.display())))),
+    };
+
+    if replace {
+        remove(&target, &existing)?;
+        report.replaced.push(target.clone());
+        Ok(Some(target))
+    } else {
+        report.skipped.push(target);
+        Ok(None)
+    }
+}
@@ -1,140 +1,952 @@
-use std::fs;
+use std::{collections::{HashMap, HashSet}, ffi::OsString, fs, io, path::{Path, PathBuf}, sync::{Arc, Mutex}};
 
-use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use glob::{MatchOptions, Pattern};
+use ignore::gitignore::Gitignore;
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{Serialize, Deserialize};
 
-use crate::{huffman::{self, HuffmanTree}, lz77};
+use crate::{compress::{CompressOptions, DEFAULT_BLOCK_SIZE}, dedup::Dedup, error::{Error, PathContext, Result}, extract::{self, ExtractOptions, ExtractReport}, filter::{Filter, IgnoreFiles}, huffman::{self, HuffmanTree}, lz77, metadata::Metadata, name::Name, progress::{Progress, Tracker}, reader::ArchiveReader, solid::Blocks, writer::ArchiveWriter};
+
+struct Scan {
+    char_counts: [u64; 256],
+    files: u64,
+    bytes: u64,
+}
+
+impl Default for Scan {
+    fn default() -> Self {
+        Scan { char_counts: [0; 256], files: 0, bytes: 0 }
+    }
+}
+
+impl Scan {
+    fn merge(mut self, other: Scan) -> Self {
+        for i in 0..256 {
+            self.char_counts[i] += other.char_counts[i];
+        }
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum Archive {
     File{
-        name: String,
-        content: FileData,
+        name: Name,
+        size: u64,
+        /// CRC32 of the contents, zero in encrypted archives, where it would tell something
+        /// about the contents and authentication already detects any change.
+        checksum: u32,
+        metadata: Metadata,
+        content: Content,
     },
     Directory{
-        name: String,
+        name: Name,
+        metadata: Metadata,
         children: Vec<Archive>,
     },
+    Symlink {
+        name: Name,
+        target: Name,
+    },
     Root {
-        name: String,
+        name: Name,
         children: Vec<Archive>,
         tree: Vec<u8>,
+        /// Stored data of the solid blocks, numbered by their position.
+        blocks: Vec<Content>,
     },
 }
 
 impl Archive {
-    pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+    pub(crate) fn not_root() -> Error {
+        Error::InvalidParameter("archive must start with a root entry".to_string())
     }
 
-    pub fn deserialize(input: &[u8]) -> Self {
-        bincode::deserialize(input).unwrap()
+    pub fn get_name(&self) -> &Name {
+        match self {
+            Archive::File { name, .. } => name,
+            Archive::Directory { name, .. } => name,
+            Archive::Symlink { name, .. } => name,
+            Archive::Root { name, .. } => name,
+        }
     }
 
-    pub fn get_name(&self) -> String {
+    pub fn metadata(&self) -> Option<&Metadata> {
         match self {
-            Archive::File { name, .. } => name.clone(),
-            Archive::Directory { name, .. } => name.clone(),
-            Archive::Root { name, .. } => name.clone(),
+            Archive::File { metadata, .. } | Archive::Directory { metadata, .. } => Some(metadata),
+            Archive::Symlink { .. } | Archive::Root { .. } => None,
         }
     }
 
-    fn count_chars(path: &str) -> [u64; 256] {
-        let full_path = fs::canonicalize(path).unwrap();
-        if full_path.is_file() {
-            let file = fs::read(path).unwrap();
-            if file.len() > 5000 {
-                return [0; 256];
-            }
-            let mut char_counts = [0; 256];
-            for &c in &file {
-                char_counts[c as usize] += 1;
+    /// Calls `f` with the path and entry of everything below the root.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&Name, &'a Archive)) {
+        self.walk_rec(&Name::default(), f);
+    }
+
+    fn walk_rec<'a>(&'a self, prefix: &Name, f: &mut impl FnMut(&Name, &'a Archive)) {
+        if let Archive::Root { children, .. } = self {
+            children.iter().for_each(|child| child.walk_rec(prefix, f));
+            return;
+        }
+        let path = prefix.join(self.get_name());
+        f(&path, self);
+        if let Archive::Directory { children, .. } = self {
+            children.iter().for_each(|child| child.walk_rec(&path, f));
+        }
+    }
+
+    /// Sets the checksum of every file to zero.
+    pub(crate) fn clear_checksums(&mut self) {
+        match self {
+            Archive::File { checksum, .. } => *checksum = 0,
+            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.iter_mut().for_each(Archive::clear_checksums),
+            Archive::Symlink { .. } => (),
+        }
+    }
+
+    pub fn select(&self, patterns: &[Pattern]) -> Result<Self> {
+        let Archive::Root { name, children, tree, blocks } = self else {
+            return Err(Self::not_root());
+        };
+        // Duplicates keep pointing at their original, which stays readable through the full index.
+        Ok(Archive::Root {
+            name: name.clone(),
+            children: children.iter().filter_map(|child| child.select_rec(&Name::default(), patterns)).collect(),
+            tree: tree.clone(),
+            blocks: blocks.clone(),
+        })
+    }
+
+    fn select_rec(&self, prefix: &Name, patterns: &[Pattern]) -> Option<Self> {
+        let path = prefix.join(self.get_name());
+        if Self::matches_any(patterns, &path) {
+            return Some(self.clone());
+        }
+        match self {
+            Archive::Directory { name, metadata, children } => {
+                let children = children.iter()
+                    .filter_map(|child| child.select_rec(&path, patterns))
+                    .collect::<Vec<_>>();
+                (!children.is_empty()).then(|| Archive::Directory { name: name.clone(), metadata: *metadata, children })
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether `path` matches one of `patterns`, where wildcards never match a `/`.
+    fn matches_any(patterns: &[Pattern], path: &Name) -> bool {
+        let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+        let path = path.to_string();
+        patterns.iter().any(|pattern| pattern.matches_with(&path, options))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.is_empty(),
+            Archive::File { .. } | Archive::Symlink { .. } => false,
+        }
+    }
+
+    fn entry_name(path: &Path) -> Result<Name> {
+        let full_path = match path.file_name() {
+            Some(_) => path.to_path_buf(),
+            None => fs::canonicalize(path).at(path)?,
+        };
+        let name = full_path.file_name()
+            .ok_or_else(|| Error::InvalidParameter(format!("{} has no file name", path.display())))?;
+        Name::from_os_str(name)
+    }
+
+    fn entry_kind(path: &Path, options: &CompressOptions) -> Result<Option<EntryKind>> {
+        let metadata = fs::symlink_metadata(path).at(path)?;
+        let metadata = match metadata.file_type().is_symlink() {
+            true if !options.follow_symlinks => return Ok(Some(EntryKind::Symlink)),
+            // A dangling link has nothing to follow, so it is kept as a link.
+            true => match fs::metadata(path) {
+                Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Some(EntryKind::Symlink)),
+                metadata => metadata.at(path)?,
+            },
+            false => metadata,
+        };
+        Ok(if metadata.is_dir() {
+            Some(EntryKind::Directory)
+        } else if metadata.is_file() {
+            Some(EntryKind::File)
+        } else {
+            None
+        })
+    }
+
+    /// Lists the children of the directory at `path` that pass `filter`, with their names,
+    /// sorted by name, and returns the ignore files in effect for them.
+    fn child_names(path: &Path, archive_path: &Name, options: &CompressOptions, filter: &Filter, ignore_files: &[Arc<Gitignore>]) -> Result<(Vec<(OsString, Name)>, IgnoreFiles)> {
+        let ignore_files = filter.enter(path, ignore_files)?;
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path).at(path)? {
+            let file_name = entry.at(path)?.file_name();
+            let name = Name::from_os_str(&file_name)?;
+            let child = path.join(&file_name);
+            let is_directory = matches!(Self::entry_kind(&child, options)?, Some(EntryKind::Directory));
+            if !filter.excludes(&child, &archive_path.join(&name), is_directory, &ignore_files)? {
+                names.push((file_name, name));
             }
-            return char_counts;
         }
+        names.sort_by(|(_, a), (_, b)| a.cmp(b));
+        Ok((names, ignore_files))
+    }
 
-        fs::read_dir(path).unwrap()
-            .map(|entry| entry.unwrap().path().file_name().unwrap().to_str().unwrap().to_string())
-            .filter(|child_path| !child_path.ends_with(".tmy"))
-            .map(|child_path| Self::count_chars(&(path.to_string() + "/" + &child_path)))
-            .fold([0; 256], |mut acc, counts| {
-                for i in 0..256 {
-                    acc[i] += counts[i];
+    fn count_chars(path: &Path, archive_path: &Name, options: &CompressOptions, filter: &Filter, ignore_files: &[Arc<Gitignore>], ancestors: &mut Vec<PathBuf>) -> Result<Scan> {
+        match Self::entry_kind(path, options)? {
+            Some(EntryKind::File) => {
+                let size = fs::metadata(path).at(path)?.len();
+                let mut scan = Scan { char_counts: [0; 256], files: 1, bytes: size };
+                if size > 5000 {
+                    return Ok(scan);
                 }
-                acc
-            })
-        
+                for &c in &fs::read(path).at(path)? {
+                    scan.char_counts[c as usize] += 1;
+                }
+                Ok(scan)
+            },
+            Some(EntryKind::Directory) => {
+                if options.follow_symlinks {
+                    let full_path = fs::canonicalize(path).at(path)?;
+                    if ancestors.contains(&full_path) {
+                        return Err(Error::InvalidParameter(format!("{} links back to {}", path.display(), full_path.display())));
+                    }
+                    ancestors.push(full_path);
+                }
+                let (children, ignore_files) = Self::child_names(path, archive_path, options, filter, ignore_files)?;
+                let scan = children.into_iter()
+                    .map(|(file_name, name)| Self::count_chars(&path.join(file_name), &archive_path.join(&name), options, filter, &ignore_files, ancestors))
+                    .try_fold(Scan::default(), |acc, scan| Ok::<_, Error>(acc.merge(scan?)))?;
+                if options.follow_symlinks {
+                    ancestors.pop();
+                }
+                Ok(scan)
+            },
+            Some(EntryKind::Symlink) | None => Ok(Scan::default()),
+        }
     }
 
     fn contains_fixed_huffman(&self) -> bool {
         match self {
-            Archive::File { content, .. } => matches!(content, FileData::FixedHuffman { .. }),
-            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.iter().any(|child| child.contains_fixed_huffman()),
+            Archive::File { content: Content::Stored { codec, .. }, .. } => codec == "FixedHuffman",
+            Archive::File { .. } => false,
+            Archive::Directory { children, .. } => children.iter().any(|child| child.contains_fixed_huffman()),
+            Archive::Root { children, blocks, .. } => children.iter().any(|child| child.contains_fixed_huffman())
+                || blocks.iter().any(|block| block.codec() == "FixedHuffman"),
+            Archive::Symlink { .. } => false,
+        }
+    }
+
+    /// The name `read_paths` gives the root for these inputs.
+    pub fn archive_name<P: AsRef<Path>>(paths: &[P]) -> Result<Name> {
+        match paths {
+            [path] => Self::entry_name(path.as_ref()),
+            _ => Ok(Name::from("archive")),
         }
     }
 
-    pub fn read_directory(path: &str) -> Self {
-        let char_counts = Self::count_chars(path);
-        let mut tree = huffman::HuffmanTree::from_counts(char_counts);
-        let r = Self::read_directory_rec(path, &tree);
-        match &r {
-            Archive::Directory { name, children } => {
-                if !r.contains_fixed_huffman() {
-                    tree = HuffmanTree {
-                        children: vec![],
-                        character: None,
+    fn scan<P: AsRef<Path>>(paths: &[P], options: &CompressOptions, filter: &Filter) -> Result<Scan> {
+        paths.iter()
+            .map(|path| Self::count_chars(path.as_ref(), &Self::entry_name(path.as_ref())?, options, filter, &[], &mut Vec::new()))
+            .try_fold(Scan::default(), |acc, scan| Ok::<_, Error>(acc.merge(scan?)))
+    }
+
+    fn input_names<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<Name>> {
+        let mut names = Vec::new();
+        for path in paths {
+            let name = Self::entry_name(path.as_ref())?;
+            if names.contains(&name) {
+                return Err(Error::InvalidParameter(format!("duplicate entry name: {}", name)));
+            }
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    fn read_children<P: AsRef<Path> + Sync>(paths: &[P], reading: &Reading) -> Result<Vec<Self>> {
+        paths.par_iter()
+            .map(|path| Self::read_directory_rec(path.as_ref(), &Name::default(), Self::entry_name(path.as_ref())?, &[], reading))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    /// Builds a root once every entry has been found: stores the data of new files, fixes
+    /// duplicates whose original is gone and keeps the fixed tree only if some data was
+    /// encoded with it.
+    fn root(name: Name, children: Vec<Self>, reading: &Reading) -> Result<Self> {
+        let mut archive = Archive::Root { name, children, tree: Vec::new(), blocks: Vec::new() };
+        archive.store_pending(reading)?;
+        let solid_blocks = reading.blocks.finish()?;
+        if let Archive::Root { blocks, .. } = &mut archive {
+            *blocks = solid_blocks;
+        }
+        if archive.contains_fixed_huffman() {
+            if let Archive::Root { tree, .. } = &mut archive {
+                *tree = reading.tree.better_serialize()?;
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Compresses `paths`, storing file data in `writer` as it is produced, and
+    /// returns the index to finish the archive with.
+    /// Directory entries are sorted by name, and unless it is encrypted or records access
+    /// times, the archive comes out the same byte for byte however many threads compress
+    /// it and however often the tree is read in between.
+    pub fn read_paths<P: AsRef<Path> + Sync>(paths: &[P], options: &CompressOptions, writer: &ArchiveWriter, progress: &dyn Progress) -> Result<Self> {
+        let filter = Filter::new(options)?;
+        let scan = Self::scan(paths, options, &filter)?;
+        let tree = huffman::HuffmanTree::from_counts(scan.char_counts);
+        Self::input_names(paths)?;
+
+        let reading = Reading::new(&tree, options, filter, writer, None, Tracker::new(progress, scan.files, scan.bytes));
+        Self::root(Self::archive_name(paths)?, Self::read_children(paths, &reading)?, &reading)
+    }
+
+    /// Rebuilds the index of `previous` with `paths` added to the root. Entries with the
+    /// same name are only allowed with `replace`, and are then read again, reusing the data
+    /// of files whose size and modification time did not change.
+    pub(crate) fn merge_paths<P: AsRef<Path> + Sync>(previous: &ArchiveReader, paths: &[P], options: &CompressOptions, writer: &ArchiveWriter, progress: &dyn Progress, replace: bool) -> Result<Self> {
+        let Archive::Root { name, children, tree, .. } = previous.index() else {
+            return Err(Self::not_root());
+        };
+        let names = Self::input_names(paths)?;
+        if let Some(existing) = children.iter().find(|child| names.contains(child.get_name())).filter(|_| !replace) {
+            return Err(Error::InvalidParameter(format!("{} is already in the archive, use update to replace it", existing.get_name())));
+        }
+
+        let filter = Filter::new(options)?;
+        let scan = Self::scan(paths, options, &filter)?;
+        // The fixed tree can only be rebuilt while no kept entry depends on it.
+        let tree = match tree.is_empty() {
+            true => huffman::HuffmanTree::from_counts(scan.char_counts),
+            false => previous.index().fixed_tree()?,
+        };
+        let kept = children.iter()
+            .filter(|child| !names.contains(child.get_name()))
+            .collect::<Vec<_>>();
+        let (files, bytes) = kept.iter()
+            .map(|child| child.totals())
+            .fold((scan.files, scan.bytes), |(files, bytes), (child_files, child_bytes)| (files + child_files, bytes + child_bytes));
+
+        let reading = Reading::new(&tree, options, filter, writer, Some(previous), Tracker::new(progress, files, bytes));
+        let mut children = kept.iter()
+            .map(|child| child.reuse(&Name::default(), &reading))
+            .collect::<Result<Vec<_>>>()?;
+        children.extend(Self::read_children(paths, &reading)?);
+        Self::root(name.clone(), children, &reading)
+    }
+
+    /// Rebuilds the index of `previous` without the entries matching `patterns`, which
+    /// are returned alongside it.
+    pub(crate) fn remove_matching(previous: &ArchiveReader, patterns: &[Pattern], writer: &ArchiveWriter, progress: &dyn Progress) -> Result<(Self, Vec<Name>)> {
+        let Archive::Root { name, children, blocks, .. } = previous.index() else {
+            return Err(Self::not_root());
+        };
+        let mut removed = Vec::new();
+        let children = children.iter()
+            .filter_map(|child| child.prune(&Name::default(), patterns, &mut removed))
+            .collect::<Vec<_>>();
+        let (files, bytes) = children.iter()
+            .map(Archive::totals)
+            .fold((0, 0), |(files, bytes), (child_files, child_bytes)| (files + child_files, bytes + child_bytes));
+
+        let tree = previous.index().fixed_tree()?;
+        // Files of blocks that lose one are put into new blocks.
+        let options = CompressOptions { solid_block_size: (!blocks.is_empty()).then_some(DEFAULT_BLOCK_SIZE), ..CompressOptions::default() };
+        let reading = Reading::new(&tree, &options, Filter::new(&options)?, writer, Some(previous), Tracker::new(progress, files, bytes));
+        let children = children.iter()
+            .map(|child| child.reuse(&Name::default(), &reading))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((Self::root(name.clone(), children, &reading)?, removed))
+    }
+
+    fn prune(&self, prefix: &Name, patterns: &[Pattern], removed: &mut Vec<Name>) -> Option<Self> {
+        let path = prefix.join(self.get_name());
+        if Self::matches_any(patterns, &path) {
+            removed.push(path);
+            return None;
+        }
+        match self {
+            Archive::Directory { name, metadata, children } => Some(Archive::Directory {
+                name: name.clone(),
+                metadata: *metadata,
+                children: children.iter().filter_map(|child| child.prune(&path, patterns, removed)).collect(),
+            }),
+            _ => Some(self.clone()),
+        }
+    }
+
+    /// Keeps this entry of the previous archive. The stored data of every file below it is
+    /// copied into the new one by `store_pending`.
+    fn reuse(&self, prefix: &Name, reading: &Reading) -> Result<Self> {
+        let archive_path = prefix.join(self.get_name());
+        Ok(match self {
+            Archive::File { .. } => {
+                reading.pending.lock().unwrap().insert(archive_path, Pending::Copy);
+                self.clone()
+            },
+            Archive::Directory { name, metadata, children } => Archive::Directory {
+                name: name.clone(),
+                metadata: *metadata,
+                children: children.iter()
+                    .map(|child| child.reuse(&archive_path, reading))
+                    .collect::<Result<Vec<_>>>()?,
+            },
+            Archive::Symlink { .. } => self.clone(),
+            Archive::Root { .. } => return Err(Self::not_root()),
+        })
+    }
+
+    /// Stores the files found while walking the inputs in a way that does not depend on
+    /// scheduling. Files are read a window at a time: in parallel, where each is hashed and
+    /// compressed unless it goes into a solid block, then in walk order, where duplicates
+    /// are found, dropping their compressed data, and data is written. Apart from the scan
+    /// for the fixed tree, which also reads files of up to 5000 bytes, this is the only read.
+    /// Kept files are copied from the previous archive, except those in a solid block that
+    /// lost a file, which go through the same steps so no removed data stays behind.
+    fn store_pending(&mut self, reading: &Reading) -> Result<()> {
+        let mut pending = std::mem::take(&mut *reading.pending.lock().unwrap());
+        let mut order = Vec::new();
+        self.walk(&mut |path, _| if let Some(file) = pending.remove(path) {
+            order.push((path.clone(), file));
+        });
+        let kept = order.iter()
+            .filter(|(_, file)| matches!(file, Pending::Copy))
+            .map(|(archive_path, _)| archive_path.clone())
+            .collect::<HashSet<_>>();
+        let stale = reading.previous.map(|previous| previous.index().stale_blocks(&kept)).unwrap_or_default();
+
+        let mut files = Vec::new();
+        let mut hard_links = Vec::new();
+        for (archive_path, file) in order {
+            match file {
+                Pending::Copy => {
+                    let Some(Archive::File { size, content, .. }) = self.find_mut(&archive_path) else {
+                        continue;
                     };
-                    println!("No fixed huffman found");
+                    let data = match &*content {
+                        // Duplicates whose original was removed or replaced need their own copy of its data.
+                        Content::Reference { path: original, .. } if !kept.contains(original) => reading.previous_content(&archive_path)?.clone(),
+                        data => data.clone(),
+                    };
+                    match data {
+                        Content::Solid { block, .. } if stale.contains(&block) => files.push((archive_path, Origin::Previous)),
+                        data => {
+                            *content = reading.copy(&data)?;
+                            reading.tracker.file_done(*size);
+                        },
+                    }
+                },
+                Pending::Read { path, metadata } => match reading.dedup.claim_inode(&metadata, &archive_path) {
+                    Some(original) => {
+                        reading.tracker.file_done(metadata.len());
+                        hard_links.push((archive_path, original));
+                    },
+                    None => files.push((archive_path, Origin::Disk(path))),
+                },
+            }
+        }
+
+        let mut stored = Vec::with_capacity(files.len());
+        for window in files.chunks(2 * rayon::current_num_threads()) {
+            let read = window.par_iter()
+                .map(|(archive_path, origin)| ReadFile::read(archive_path, origin, reading))
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut jobs = Vec::new();
+            for ((archive_path, _), file) in window.iter().zip(read) {
+                reading.tracker.file_done(file.size);
+                let content = match (reading.dedup.claim_content(file.hash, archive_path), file.data) {
+                    (Some(original), _) => Some(Content::Reference { path: original, hard_link: false }),
+                    (None, Payload::Raw(data)) => {
+                        let ((block, start), closed) = reading.blocks.append(data);
+                        jobs.extend(closed.map(|(block, data)| (Target::Block(block), Payload::Raw(data))));
+                        Some(Content::Solid { block, start })
+                    },
+                    (None, data) => {
+                        jobs.push((Target::File(stored.len()), data));
+                        None
+                    },
+                };
+                stored.push((file.size, file.checksum, content));
+            }
+            Self::store_jobs(jobs, &mut stored, reading)?;
+        }
+        let last_block = reading.blocks.close().map(|(block, data)| (Target::Block(block), Payload::Raw(data)));
+        Self::store_jobs(last_block.into_iter().collect(), &mut stored, reading)?;
+
+        for ((archive_path, _), (size, checksum, content)) in files.into_iter().zip(stored) {
+            let content = content.ok_or_else(|| Error::InvalidParameter(format!("{} was not stored", archive_path)))?;
+            if let Some(Archive::File { size: stored_size, checksum: stored_checksum, content: stored_content, .. }) = self.find_mut(&archive_path) {
+                (*stored_size, *stored_checksum, *stored_content) = (size, checksum, content);
+            }
+        }
+        for (archive_path, original) in hard_links {
+            let Some(&Archive::File { size, checksum, .. }) = self.find(&original) else {
+                return Err(Error::InvalidParameter(format!("{} was not stored", original)));
+            };
+            if let Some(Archive::File { size: stored_size, checksum: stored_checksum, content: stored_content, .. }) = self.find_mut(&archive_path) {
+                (*stored_size, *stored_checksum) = (size, checksum);
+                *stored_content = Content::Reference { path: original, hard_link: true };
+            }
+        }
+        Ok(())
+    }
+
+    /// Compresses what is left to compress of `jobs` in parallel, then writes them in order.
+    fn store_jobs(jobs: Vec<(Target, Payload)>, stored: &mut [(u64, u32, Option<Content>)], reading: &Reading) -> Result<()> {
+        let encoded = jobs.into_par_iter()
+            .map(|(target, data)| Ok((target, data.encode(reading.fixed_tree())?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (target, data) in encoded {
+            let content = reading.writer.store(&data)?;
+            match target {
+                Target::File(index) => stored[index].2 = Some(content),
+                Target::Block(block) => reading.blocks.store(block, content),
+            }
+        }
+        Ok(())
+    }
+
+    /// The solid blocks holding data of a file that is not among `kept`.
+    fn stale_blocks(&self, kept: &HashSet<Name>) -> HashSet<u64> {
+        let mut stale = HashSet::new();
+        self.walk(&mut |path, entry| if let Archive::File { content: Content::Solid { block, .. }, .. } = entry {
+            if !kept.contains(path) {
+                stale.insert(*block);
+            }
+        });
+        stale
+    }
+
+    fn find_mut(&mut self, path: &Name) -> Option<&mut Archive> {
+        path.components().try_fold(self, |entry, name| match entry {
+            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.iter_mut().find(|child| child.get_name().as_bytes() == name),
+            _ => None,
+        })
+    }
+
+    fn read_directory_rec(path: &Path, prefix: &Name, name: Name, ignore_files: &[Arc<Gitignore>], reading: &Reading) -> Result<Option<Self>> {
+        let archive_path = prefix.join(&name);
+        match Self::entry_kind(path, reading.options)? {
+            Some(EntryKind::File) => {
+                let file_metadata = fs::metadata(path).at(path)?;
+                let metadata = reading.metadata(path)?;
+                if let Some(previous @ Archive::File { size, metadata: previous_metadata, .. }) = reading.previous.and_then(|previous| previous.index().find(&archive_path)) {
+                    if *size == file_metadata.len() && previous_metadata.modified == metadata.modified {
+                        let mut entry = previous.reuse(prefix, reading)?;
+                        if let Archive::File { metadata: reused_metadata, .. } = &mut entry {
+                            *reused_metadata = metadata;
+                        }
+                        return Ok(Some(entry));
+                    }
                 }
-                Archive::Root { name: name.to_string(), children: children.clone(), tree: tree.better_serialize() }
+
+                let size = file_metadata.len();
+                reading.pending.lock().unwrap().insert(archive_path, Pending::Read { path: path.to_path_buf(), metadata: file_metadata });
+                // Size, checksum and content are filled in by `store_pending`.
+                Ok(Some(Self::File {
+                    name,
+                    size,
+                    checksum: 0,
+                    metadata,
+                    content: Content::Reference { path: Name::default(), hard_link: false },
+                }))
+            },
+            Some(EntryKind::Directory) => {
+                let (children, ignore_files) = Self::child_names(path, &archive_path, reading.options, &reading.filter, ignore_files)?;
+                let children = children.into_par_iter()
+                    .map(|(file_name, name)| Self::read_directory_rec(&path.join(file_name), &archive_path, name, &ignore_files, reading))
+                    .filter_map(Result::transpose)
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Some(Self::Directory {
+                    name,
+                    metadata: reading.metadata(path)?,
+                    children,
+                }))
             },
-            _ => panic!("Root must be a directory"),        
+            Some(EntryKind::Symlink) => {
+                let target = fs::read_link(path).at(path)?;
+                Ok(Some(Self::Symlink { name, target: Name::from_os_str(target.as_os_str())? }))
+            },
+            None => Ok(None),
         }
+    }
 
+    /// Looks up the entry at `path`, relative to the root like the paths passed to `walk`.
+    pub fn find(&self, path: &Name) -> Option<&Archive> {
+        path.components().try_fold(self, |entry, name| match entry {
+            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.iter().find(|child| child.get_name().as_bytes() == name),
+            _ => None,
+        })
     }
 
-    pub fn read_directory_rec(path: &str, tree: &HuffmanTree) -> Self {
-        let full_path = fs::canonicalize(path).unwrap();
-        let dir_name = full_path.file_name().unwrap().to_str().unwrap();
-        if full_path.is_file() {
-            return Self::File {
-                name: dir_name.to_string(),
-                content: FileData::read_and_encode(path, tree),
+    /// Follows duplicate references from the `File` entry at `path` to the stored
+    /// content holding its data.
+    pub fn resolve<'a>(&'a self, path: &Name, entry: &'a Archive) -> Result<&'a Content> {
+        let mut current = entry;
+        // An inode reference can point at a content reference, never deeper.
+        for _ in 0..3 {
+            match current {
+                Archive::File { content: Content::Reference { path: original, .. }, .. } => {
+                    current = self.find(original)
+                        .ok_or_else(|| Error::Corrupt(format!("{} refers to missing entry {}", path, original)))?;
+                },
+                Archive::File { content, .. } => return Ok(content),
+                _ => break,
             }
         }
+        Err(Error::Corrupt(format!("{} does not refer to a file", path)))
+    }
 
-        let children = fs::read_dir(path).unwrap()
-            .map(|entry| entry.unwrap().path().file_name().unwrap().to_str().unwrap().to_string())
-            .filter(|child_path| !child_path.ends_with(".tmy"))
-            .par_bridge()
-            .map(|child_path| Self::read_directory_rec(&(path.to_string() + "/" + &child_path), tree))
-            .collect::<Vec<_>>();
+    /// The stored data of solid block `block`.
+    pub fn block(&self, block: u64) -> Result<&Content> {
+        let Archive::Root { blocks, .. } = self else {
+            return Err(Self::not_root());
+        };
+        blocks.get(block as usize)
+            .filter(|content| matches!(content, Content::Stored { .. }))
+            .ok_or_else(|| Error::Corrupt(format!("solid block {} is missing", block)))
+    }
 
-        Self::Directory {
-            name: dir_name.to_string(),
-            children,
+    pub fn fixed_tree(&self) -> Result<HuffmanTree> {
+        match self {
+            Archive::Root { tree, .. } if tree.is_empty() => Ok(HuffmanTree {
+                children: vec![],
+                character: None,
+            }),
+            Archive::Root { tree, .. } => huffman::HuffmanTree::better_deserialize(tree),
+            _ => Err(Self::not_root()),
         }
     }
 
-    pub fn write_directory(&self, path: &str) {
+    fn totals(&self) -> (u64, u64) {
         match self {
-            Archive::Root { tree, .. } => {
-                self.write_directory_rec(path, &huffman::HuffmanTree::better_deserialize(tree));},
-            _ => panic!("Root must be a directory"),
+            Archive::File { size, .. } => (1, *size),
+            Archive::Symlink { .. } => (0, 0),
+            Archive::Directory { children, .. } | Archive::Root { children, .. } => children.iter()
+                .map(Archive::totals)
+                .fold((0, 0), |(files, bytes), (child_files, child_bytes)| (files + child_files, bytes + child_bytes)),
+        }
+    }
+
+    /// Extracts this root, which is either the index of `reader` or a selection from it.
+    pub fn write_directory(&self, reader: &ArchiveReader, path: &Path, options: &ExtractOptions, progress: &dyn Progress) -> Result<ExtractReport> {
+        let Archive::Root { children, .. } = self else {
+            return Err(Self::not_root());
+        };
+        let (files, bytes) = self.totals();
+        let extraction = Extraction {
+            reader,
+            options,
+            tracker: Tracker::new(progress, files, bytes),
+            written: Mutex::new(HashMap::new()),
+            hard_links: Mutex::new(Vec::new()),
+            directories: Mutex::new(Vec::new()),
+        };
+
+        fs::create_dir_all(path).at(path)?;
+        let mut report = Self::write_children(children, &Name::default(), path, &extraction)?;
+
+        // Hard links need their original on disk, so they are created once everything else is.
+        let hard_links = std::mem::take(&mut *extraction.hard_links.lock().unwrap());
+        for (archive_path, target, entry) in hard_links {
+            let modified = entry.metadata().map(|metadata| metadata.modified.into());
+            let Some(target) = extract::resolve_conflict(target, false, modified, options, &mut report)? else {
+                let (files, bytes) = entry.totals();
+                extraction.tracker.advance(files, bytes);
+                continue;
+            };
+            let original = match entry {
+                Archive::File { content: Content::Reference { path, .. }, .. } => extraction.written.lock().unwrap().get(path).cloned(),
+                _ => None,
+            };
+            match original {
+                Some(original) => {
+                    fs::hard_link(&original, &target).at(&target)?;
+                    let (files, bytes) = entry.totals();
+                    extraction.tracker.advance(files, bytes);
+                },
+                None => extraction.write_file(&archive_path, entry, &target)?,
+            }
+        }
+
+        // Directory times and permissions go last, deepest first, so writing their contents does not undo them.
+        let mut directories = extraction.directories.into_inner().unwrap();
+        directories.sort_by_key(|(target, _)| std::cmp::Reverse(target.components().count()));
+        for (target, metadata) in directories {
+            metadata.apply(&target, options.restore_ownership)?;
+        }
+        Ok(report)
+    }
+
+    #[cfg(unix)]
+    fn create_symlink(link_target: &Name, path: &Path) -> Result<()> {
+        std::os::unix::fs::symlink(link_target.to_os_string()?, path).at(path)
+    }
+
+    #[cfg(not(unix))]
+    fn create_symlink(_: &Name, path: &Path) -> Result<()> {
+        Err(Error::InvalidParameter(format!("{}: symbolic links are not supported on this platform", path.display())))
+    }
+
+    /// Extracts `children` into `path`. Only the first of several children with the same
+    /// name is written, the others could replace it with a symbolic link while it is in use.
+    fn write_children<'a>(children: &'a [Archive], prefix: &Name, path: &Path, extraction: &Extraction<'a>) -> Result<ExtractReport> {
+        let mut names = HashSet::new();
+        let first = children.iter()
+            .map(|child| names.insert(child.get_name()))
+            .collect::<Vec<_>>();
+        children.par_iter()
+            .zip(first)
+            .map(|(child, first)| match first {
+                true => child.write_directory_rec(prefix, path, extraction),
+                false => Ok(child.refuse(prefix, "duplicate entry name", extraction)),
+            })
+            .try_reduce(ExtractReport::default, |a, b| Ok(a.merge(b)))
+    }
+
+    fn refuse(&self, prefix: &Name, reason: &str, extraction: &Extraction) -> ExtractReport {
+        let (files, bytes) = self.totals();
+        extraction.tracker.advance(files, bytes);
+        ExtractReport {
+            refused: vec![(prefix.join(self.get_name()), reason.to_string())],
+            ..ExtractReport::default()
         }
     }
 
-    pub fn write_directory_rec(&self, path: &str, tree: &HuffmanTree) {
+    fn write_directory_rec<'a>(&'a self, prefix: &Name, path: &Path, extraction: &Extraction<'a>) -> Result<ExtractReport> {
+        if let Some(reason) = extract::unsafe_name(self.get_name()) {
+            return Ok(self.refuse(prefix, reason, extraction));
+        }
+        let mut report = ExtractReport::default();
+        let archive_path = prefix.join(self.get_name());
+        let target = path.join(self.get_name().to_os_string()?);
+        if let Archive::File { content: Content::Reference { hard_link: true, .. }, .. } = self {
+            extraction.hard_links.lock().unwrap().push((archive_path, target, self));
+            return Ok(report);
+        }
+
+        let is_directory = matches!(self, Archive::Directory { .. });
+        let modified = self.metadata().map(|metadata| metadata.modified.into());
+        let Some(target) = extract::resolve_conflict(target, is_directory, modified, extraction.options, &mut report)? else {
+            let (files, bytes) = self.totals();
+            extraction.tracker.advance(files, bytes);
+            return Ok(report);
+        };
         match self {
-            Archive::File { name, content } if fs::metadata(format!("{}/{}", path, name)).is_err() => {
-                let decoded = content.decode(tree);
-                fs::write(path.to_string() + "/" + name, &decoded).unwrap();
-            },
-            Archive::Directory { name, children } | Archive::Root { name, children, .. } if fs::metadata(format!("{}/{}", path, name)).is_err() => {
-                fs::create_dir(path.to_string() + "/" + name).unwrap_or(());
-                children.par_iter().for_each(|child| child.write_directory_rec(&(path.to_string() + "/" + name), tree));
+            Archive::File { .. } => extraction.write_file(&archive_path, self, &target)?,
+            Archive::Directory { metadata, children, .. } => {
+                // Never follow a symbolic link here, everything below would end up wherever it points.
+                if !fs::symlink_metadata(&target).is_ok_and(|existing| existing.is_dir()) {
+                    fs::create_dir(&target).at(&target)?;
+                }
+                report = report.merge(Self::write_children(children, &archive_path, &target, extraction)?);
+                extraction.directories.lock().unwrap().push((target, *metadata));
             },
-            Archive::File { name, .. } | Archive::Directory { name, .. } | Archive::Root { name, .. } => println!("{} existiert bereits", name),
+            Archive::Symlink { target: link_target, .. } => Self::create_symlink(link_target, &target)?,
+            Archive::Root { .. } => return Err(Self::not_root()),
+        }
+        Ok(report)
+    }
+}
+
+/// State shared by all threads while compressing into one archive.
+struct Reading<'a> {
+    tree: &'a HuffmanTree,
+    options: &'a CompressOptions,
+    filter: Filter,
+    dedup: Dedup,
+    blocks: Blocks,
+    /// Files found by the walk by archive path, stored by `store_pending` once it is complete.
+    pending: Mutex<HashMap<Name, Pending>>,
+    writer: &'a ArchiveWriter,
+    /// The archive being rewritten, whose data can be copied instead of compressed again.
+    previous: Option<&'a ArchiveReader>,
+    tracker: Tracker<'a>,
+}
+
+impl<'a> Reading<'a> {
+    fn new(tree: &'a HuffmanTree, options: &'a CompressOptions, filter: Filter, writer: &'a ArchiveWriter, previous: Option<&'a ArchiveReader>, tracker: Tracker<'a>) -> Self {
+        let blocks = Blocks::new(options.solid_block_size);
+        Reading { tree, options, filter, dedup: Dedup::default(), blocks, pending: Mutex::new(HashMap::new()), writer, previous, tracker }
+    }
+
+    /// The metadata of `path`, with the modification time standing in for the access time
+    /// unless access times are recorded.
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let mut metadata = Metadata::read(path)?;
+        if !self.options.access_times {
+            metadata.accessed = metadata.modified;
+        }
+        Ok(metadata)
+    }
+
+    /// The tree to encode small files with. Deleting from an archive that does not use one
+    /// has nothing to build it from.
+    fn fixed_tree(&self) -> Option<&'a HuffmanTree> {
+        Some(self.tree).filter(|tree| !tree.children.is_empty() || tree.character.is_some())
+    }
+
+    /// The previous archive and its entry at `path`.
+    fn previous_entry(&self, path: &Name) -> Result<(&'a ArchiveReader, &'a Archive)> {
+        let previous = self.previous.ok_or_else(|| Error::InvalidParameter("there is no archive to copy from".to_string()))?;
+        let entry = previous.index().find(path).ok_or_else(|| Error::Corrupt(format!("{} is missing from the previous archive", path)))?;
+        Ok((previous, entry))
+    }
+
+    /// The stored data the file at `path` of the previous archive refers to.
+    fn previous_content(&self, path: &Name) -> Result<&'a Content> {
+        let (previous, entry) = self.previous_entry(path)?;
+        previous.index().resolve(path, entry)
+    }
+
+    /// Decodes the file at `path` of the previous archive.
+    fn decode_previous(&self, path: &Name) -> Result<Vec<u8>> {
+        let (previous, entry) = self.previous_entry(path)?;
+        previous.decode_entry(path, entry)
+    }
+
+    /// Copies the data behind `content` from the previous archive. References are kept as they are.
+    fn copy(&self, content: &Content) -> Result<Content> {
+        let previous = self.previous.ok_or_else(|| Error::InvalidParameter("there is no archive to copy from".to_string()))?;
+        Ok(match content {
+            Content::Stored { offset, length, codec } => self.writer.store_raw(&previous.read_raw(*offset, *length)?, codec)?,
+            Content::Solid { block, start } => Content::Solid { block: self.blocks.copy(*block, previous, self.writer)?, start: *start },
+            reference => reference.clone(),
+        })
+    }
+}
+
+/// A file found by the walk whose data is still to be stored.
+enum Pending {
+    /// A new or changed file, read from `path`.
+    Read { path: PathBuf, metadata: fs::Metadata },
+    /// An unchanged file, whose data is copied from the previous archive.
+    Copy,
+}
+
+/// Where the data of a file to store is read from.
+enum Origin {
+    /// A new or changed file.
+    Disk(PathBuf),
+    /// A kept file of the previous archive, stored again because its solid block lost a file.
+    Previous,
+}
+
+/// Data of a file, compressed unless it goes into a solid block, or of a whole block.
+enum Payload {
+    Encoded(FileData),
+    Raw(Vec<u8>),
+}
+
+impl Payload {
+    fn encode(self, tree: Option<&HuffmanTree>) -> Result<FileData> {
+        match self {
+            Payload::Encoded(data) => Ok(data),
+            Payload::Raw(data) => FileData::encode(data, tree),
+        }
+    }
+}
+
+/// Where a compressed payload belongs once it is written.
+enum Target {
+    File(usize),
+    Block(u64),
+}
+
+struct ReadFile {
+    size: u64,
+    checksum: u32,
+    hash: [u8; 32],
+    data: Payload,
+}
+
+impl ReadFile {
+    fn read(archive_path: &Name, origin: &Origin, reading: &Reading) -> Result<Self> {
+        let data = match origin {
+            Origin::Disk(path) => fs::read(path).at(path)?,
+            Origin::Previous => reading.decode_previous(archive_path)?,
+        };
+        let (size, checksum, hash) = (data.len() as u64, crc32fast::hash(&data), *blake3::hash(&data).as_bytes());
+        let data = match reading.blocks.accepts(size) {
+            true => Payload::Raw(data),
+            false => Payload::Encoded(FileData::encode(data, reading.fixed_tree())?),
+        };
+        Ok(ReadFile { size, checksum, hash, data })
+    }
+}
+
+/// State shared by all threads while extracting one archive.
+struct Extraction<'a> {
+    reader: &'a ArchiveReader,
+    options: &'a ExtractOptions,
+    tracker: Tracker<'a>,
+    written: Mutex<HashMap<Name, PathBuf>>,
+    hard_links: Mutex<Vec<(Name, PathBuf, &'a Archive)>>,
+    directories: Mutex<Vec<(PathBuf, Metadata)>>,
+}
+
+impl Extraction<'_> {
+    fn write_file(&self, archive_path: &Name, entry: &Archive, target: &Path) -> Result<()> {
+        let Archive::File { size, metadata, .. } = entry else {
+            return Err(Error::InvalidParameter(format!("{} is not a file", archive_path)));
+        };
+        let decoded = self.reader.decode_entry(archive_path, entry)?;
+        fs::write(target, &decoded).at(target)?;
+        metadata.apply(target, self.options.restore_ownership)?;
+        self.written.lock().unwrap().insert(archive_path.clone(), target.to_path_buf());
+        self.tracker.file_done(*size);
+        Ok(())
+    }
+}
+
+enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// Where the data of a `File` entry lives. Offsets count from the end of the header.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum Content {
+    Stored {
+        offset: u64,
+        length: u64,
+        codec: String,
+    },
+    Reference {
+        path: Name,
+        hard_link: bool,
+    },
+    /// Part of solid block `block`, starting `start` bytes into its decoded data.
+    Solid {
+        block: u64,
+        start: u64,
+    },
+}
+
+impl Content {
+    pub fn codec(&self) -> String {
+        match self {
+            Content::Stored { codec, .. } => codec.clone(),
+            Content::Reference { hard_link: false, .. } => "Duplicate".to_string(),
+            Content::Reference { hard_link: true, .. } => "HardLink".to_string(),
+            Content::Solid { block, .. } => format!("Solid (block {})", block),
+        }
+    }
+
+    pub fn stored_size(&self) -> u64 {
+        match self {
+            Content::Stored { length, .. } => *length,
+            Content::Reference { .. } | Content::Solid { .. } => 0,
         }
     }
 }
@@ -161,59 +973,62 @@ pub enum FileData {
 }
 
 impl FileData {
-    pub fn read_and_encode(path: &str, tree: &huffman::HuffmanTree) -> Self {
-        let data = std::fs::read(path).unwrap();
-        
+    pub fn encode(data: Vec<u8>, tree: Option<&huffman::HuffmanTree>) -> Result<Self> {
         let num_bits = (data.len() + 1).ilog2();
-        let num_bits = num_bits.max(2).min(24) as u8;
+        let num_bits = num_bits.clamp(2, 24) as u8;
 
-        let lz77: lz77::LZ77 = lz77::LZ77::encode(&data, num_bits);
+        let lz77: lz77::LZ77 = lz77::LZ77::encode(&data, num_bits)?;
         let mut compressions = vec![
             FileData::LZ77Huffman { 
-                data: huffman::Huffman::encrypt(&lz77.serialize()), 
+                data: huffman::Huffman::encrypt(&lz77.serialize()?)?, 
                 bits: num_bits,
             },
             FileData::LZ77 { data: lz77, bits: num_bits },
-            FileData::Huffman { data: huffman::Huffman::encrypt(&data) },
+            FileData::Huffman { data: huffman::Huffman::encrypt(&data)? },
         ];
 
-        if data.len() < 5000 {
-            let fixed_huffman = huffman::HuffmanNoTree::encrypt(&data, tree);
+        if let Some(tree) = tree.filter(|_| data.len() < 5000) {
+            let fixed_huffman = huffman::HuffmanNoTree::encrypt(&data, tree)?;
             compressions.push(FileData::FixedHuffman { data: fixed_huffman });
         }
         compressions.push(FileData::Binary { data });
 
-        let best_format = compressions.into_iter()
-            .min_by_key(|c| c.size())
-            .unwrap();
-
-        // match &best_format {
-        //     FileData::LZ77Huffman { bits, .. } => println!("Lz77Huffman with {} bits (Path: {})", bits, path),
-        //     FileData::LZ77 { bits, .. } => println!("Lz77 with {} bits (Path: {})", bits, path),
-        //     FileData::Huffman { .. } => println!("Huffman (Path: {})", path),
-        //     FileData::Binary { .. } => println!("Binary (Path: {})", path),
-        //     FileData::FixedHuffman { .. } => println!("FixedHuffman (Path: {})", path),
-        // }
-
-        best_format
+        let sizes = compressions.iter()
+            .map(FileData::size)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(compressions.into_iter()
+            .zip(sizes)
+            .min_by_key(|(_, size)| *size)
+            .map(|(compression, _)| compression)
+            .unwrap())
     }
 
-    fn size (&self) -> usize {
+    pub fn codec(&self) -> String {
         match self {
-            FileData::LZ77Huffman { data, .. } => data.serialize().len(),
-            FileData::LZ77 { data, .. } => data.serialize().len(),
-            FileData::Huffman { data } => data.serialize().len(),
-            FileData::Binary { data } => data.len(),
-            FileData::FixedHuffman { data } => data.serialize().len(),
+            FileData::LZ77Huffman { bits, .. } => format!("LZ77Huffman ({} bits)", bits),
+            FileData::LZ77 { bits, .. } => format!("LZ77 ({} bits)", bits),
+            FileData::Huffman { .. } => "Huffman".to_string(),
+            FileData::Binary { .. } => "Binary".to_string(),
+            FileData::FixedHuffman { .. } => "FixedHuffman".to_string(),
         }
     }
 
-    pub fn decode(&self, tree: &huffman::HuffmanTree) -> Vec<u8> {
+    pub fn size(&self) -> Result<usize> {
+        Ok(match self {
+            FileData::LZ77Huffman { data, .. } => data.serialize()?.len(),
+            FileData::LZ77 { data, .. } => data.serialize()?.len(),
+            FileData::Huffman { data } => data.serialize()?.len(),
+            FileData::Binary { data } => data.len(),
+            FileData::FixedHuffman { data } => data.serialize()?.len(),
+        })
+    }
+
+    pub fn decode(&self, tree: &huffman::HuffmanTree) -> Result<Vec<u8>> {
         match self {
-            FileData::LZ77Huffman { data, bits } => lz77::LZ77::deserialize(&data.decrypt()).decode(*bits),
+            FileData::LZ77Huffman { data, bits } => lz77::LZ77::deserialize(&data.decrypt()?)?.decode(*bits),
             FileData::LZ77 { data, bits } => data.clone().decode(*bits),
             FileData::Huffman { data } => data.decrypt(),
-            FileData::Binary { data } => data.clone(),
+            FileData::Binary { data } => Ok(data.clone()),
             FileData::FixedHuffman { data } => data.decrypt(tree),
         }
     }
@@ -0,0 +1,95 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{compress::CompressOptions, error::{Error, Result}, name::Name};
+
+/// Earlier archives are left out unless a pattern includes them again.
+const DEFAULT_EXCLUDES: [&str; 2] = ["*.tmy", "*.tmy.[0-9][0-9][0-9]"];
+
+/// The `.gitignore` files in effect for a directory, outermost first.
+pub(crate) type IgnoreFiles = Vec<Arc<Gitignore>>;
+
+fn pattern_error(source: &str, error: ignore::Error) -> Error {
+    Error::InvalidParameter(format!("{}: {}", source, error))
+}
+
+/// Decides which entries below the inputs are archived, using gitignore syntax.
+/// Command line patterns and ignore files match paths relative to each input.
+pub(crate) struct Filter {
+    excludes: Gitignore,
+    includes: Option<Gitignore>,
+    git_ignore: bool,
+}
+
+impl Filter {
+    pub(crate) fn new(options: &CompressOptions) -> Result<Self> {
+        let mut excludes = GitignoreBuilder::new("");
+        for pattern in DEFAULT_EXCLUDES {
+            excludes.add_line(None, pattern).map_err(|error| pattern_error(pattern, error))?;
+        }
+        for file in &options.ignore_files {
+            if let Some(error) = excludes.add(file) {
+                return Err(pattern_error(&file.display().to_string(), error));
+            }
+        }
+        for pattern in &options.exclude {
+            excludes.add_line(None, pattern).map_err(|error| pattern_error(pattern, error))?;
+        }
+
+        let includes = match options.include.is_empty() {
+            true => None,
+            false => {
+                let mut includes = GitignoreBuilder::new("");
+                for pattern in &options.include {
+                    includes.add_line(None, pattern).map_err(|error| pattern_error(pattern, error))?;
+                }
+                Some(includes.build().map_err(|error| pattern_error("include patterns", error))?)
+            },
+        };
+        Ok(Filter {
+            excludes: excludes.build().map_err(|error| pattern_error("exclude patterns", error))?,
+            includes,
+            git_ignore: options.git_ignore,
+        })
+    }
+
+    /// Returns the ignore files in effect inside `directory`: those of its parents
+    /// plus its own `.gitignore`, if honoring them is enabled.
+    pub(crate) fn enter(&self, directory: &Path, ignore_files: &[Arc<Gitignore>]) -> Result<IgnoreFiles> {
+        let mut ignore_files = ignore_files.to_vec();
+        let path = directory.join(".gitignore");
+        if self.git_ignore && path.is_file() {
+            let (gitignore, error) = Gitignore::new(&path);
+            if let Some(error) = error {
+                return Err(pattern_error(&path.display().to_string(), error));
+            }
+            ignore_files.push(Arc::new(gitignore));
+        }
+        Ok(ignore_files)
+    }
+
+    /// Whether the entry at `path`, found at `archive_path` in the archive, is left out.
+    /// Command line patterns take precedence over `.gitignore` files, and the
+    /// innermost `.gitignore` over the ones above it. Include patterns only apply to files.
+    pub(crate) fn excludes(&self, path: &Path, archive_path: &Name, is_directory: bool, ignore_files: &[Arc<Gitignore>]) -> Result<bool> {
+        let relative = Self::relative(archive_path)?;
+        let mut matched = self.excludes.matched(&relative, is_directory);
+        for gitignore in ignore_files.iter().rev() {
+            if !matched.is_none() {
+                break;
+            }
+            matched = gitignore.matched(path, is_directory);
+        }
+        if matched.is_ignore() {
+            return Ok(true);
+        }
+        Ok(!is_directory && self.includes.as_ref().is_some_and(|includes| !includes.matched(&relative, false).is_ignore()))
+    }
+
+    /// `archive_path` without the name of the input it belongs to.
+    fn relative(archive_path: &Name) -> Result<PathBuf> {
+        let relative = archive_path.components().skip(1).collect::<Vec<_>>().join(&b'/');
+        Ok(PathBuf::from(Name(relative).to_os_string()?))
+    }
+}
@@ -0,0 +1,77 @@
+use std::io::{self, Read, Write};
+
+use crate::error::{Error, Result};
+
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"TMYA";
+pub const STREAM_MAGIC: [u8; 4] = *b"TMYS";
+/// Format version of archives written by this build.
+pub const ARCHIVE_VERSION: u16 = 8;
+/// Format version of streams written by this build, which changes independently of archives.
+pub const STREAM_VERSION: u16 = 1;
+/// File data is encrypted, see `crypto::Cipher`.
+pub const FLAG_ENCRYPTED: u32 = 1;
+/// The index is encrypted as well. Only valid together with `FLAG_ENCRYPTED`.
+pub const FLAG_ENCRYPTED_INDEX: u32 = 2;
+/// Feature flags this build understands. Readers reject anything else.
+pub const SUPPORTED_FLAGS: u32 = FLAG_ENCRYPTED | FLAG_ENCRYPTED_INDEX;
+
+/// Fixed size header in front of every archive and stream: four magic bytes,
+/// the format version and the feature flags, both little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub magic: [u8; 4],
+    pub version: u16,
+    pub flags: u32,
+}
+
+impl Header {
+    pub const LEN: usize = 10;
+
+    pub fn new(magic: [u8; 4], flags: u32) -> Self {
+        Header { magic, version: Self::current_version(magic), flags }
+    }
+
+    /// The version this build writes for `magic`.
+    pub fn current_version(magic: [u8; 4]) -> u16 {
+        match magic {
+            STREAM_MAGIC => STREAM_VERSION,
+            _ => ARCHIVE_VERSION,
+        }
+    }
+
+    /// What `magic` starts, for messages.
+    pub fn kind(magic: [u8; 4]) -> &'static str {
+        match magic {
+            STREAM_MAGIC => "stream",
+            _ => "archive",
+        }
+    }
+
+    pub fn write<W: Write>(&self, output: &mut W) -> Result<()> {
+        output.write_all(&self.magic)?;
+        output.write_all(&self.version.to_le_bytes())?;
+        output.write_all(&self.flags.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(magic: [u8; 4], input: &mut R) -> Result<Self> {
+        let kind = Self::kind(magic);
+        let mut bytes = [0; Self::LEN];
+        match input.read_exact(&mut bytes) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Err(Error::Corrupt(format!("not a tmy {}", kind))),
+            result => result?,
+        }
+        if bytes[0..4] != magic {
+            return Err(Error::Corrupt(format!("not a tmy {}", kind)));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version > Self::current_version(magic) {
+            return Err(Error::UnsupportedVersion { magic, version });
+        }
+        let flags = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        if flags & !SUPPORTED_FLAGS != 0 {
+            return Err(Error::Corrupt(format!("{} uses unknown feature flags {:#x}", kind, flags & !SUPPORTED_FLAGS)));
+        }
+        Ok(Header { magic, version, flags })
+    }
+}
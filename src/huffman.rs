@@ -1,9 +1,9 @@
-use std::{cmp::Reverse, collections::HashMap};
+use std::cmp::Reverse;
 
 use priority_queue::PriorityQueue;
 use serde::{Serialize, Deserialize};
 
-use crate::bitbuffer;
+use crate::{bitbuffer, error::{Error, Result}};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct HuffmanNoTree {
@@ -12,16 +12,19 @@ pub struct HuffmanNoTree {
 }
 
 impl HuffmanNoTree {
-    pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&self)?)
     }
 
-    pub fn encrypt(input: &Vec<u8>, tree: &HuffmanTree) -> HuffmanNoTree {
+    pub fn encrypt(input: &[u8], tree: &HuffmanTree) -> Result<HuffmanNoTree> {
         let mut lookup = (0..256).map(|_| Vec::new()).collect::<Vec<_>>();
-        tree.build_map(vec![], &mut lookup);
+        tree.build_map(vec![], &mut lookup)?;
+        if let Some(&c) = input.iter().find(|&&c| lookup[c as usize].is_empty()) {
+            return Err(Error::InvalidParameter(format!("huffman tree has no code for byte {}", c)));
+        }
 
         let (count, data) = input
-            .into_iter()
+            .iter()
             .flat_map(|&c| &lookup[c as usize])
             .fold((0usize,Vec::new()), |(indx, mut acc), c|{
                 if indx % 8 == 0 {
@@ -32,30 +35,17 @@ impl HuffmanNoTree {
                 (indx + 1, acc)
             });
 
-        HuffmanNoTree {
+        Ok(HuffmanNoTree {
             unused_bits: match count % 8 {
                 0 => 0,
                 n => 8 - n as u8,
             },
             data,
-        }
+        })
     }
 
-    pub fn decrypt(&self, tree: &HuffmanTree) -> Vec<u8> {
-        let data = &self.data;
-        let unused = self.unused_bits;
-        let mut result = Vec::new();
-        let mut input = Vec::new();
-        for i in 0..data.len() * 8 - unused as usize {
-            let indx = i / 8;
-            let bit = (i % 8) as u8;
-            input.push(data[indx] & (1 << bit) != 0);
-            if let Some(char) = tree.decrypt_char(&input) {
-                result.push(char);
-                input.clear();
-            }
-        }
-        result
+    pub fn decrypt(&self, tree: &HuffmanTree) -> Result<Vec<u8>> {
+        tree.decode_bits(&self.data, self.unused_bits)
     }
 }
 
@@ -67,39 +57,22 @@ pub struct Huffman {
 }
 
 impl Huffman {
-    pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&self)?)
     }
 
-    pub fn encrypt(input: &Vec<u8>) -> Huffman {
-        let tree = HuffmanTree::build_tree(&input);
-        let mut lookup = (0..256).map(|_| Vec::new()).collect::<Vec<_>>();
-        tree.build_map(vec![], &mut lookup);
-
-        let HuffmanNoTree { data, unused_bits } = HuffmanNoTree::encrypt(input, &tree);
-        Huffman {
-            tree: tree.better_serialize(),
+    pub fn encrypt(input: &[u8]) -> Result<Huffman> {
+        let tree = HuffmanTree::build_tree(input);
+        let HuffmanNoTree { data, unused_bits } = HuffmanNoTree::encrypt(input, &tree)?;
+        Ok(Huffman {
+            tree: tree.better_serialize()?,
             unused_bits,
             data,
-        }
+        })
     }
 
-    pub fn decrypt(&self) -> Vec<u8> {
-        let tree = HuffmanTree::better_deserialize(&self.tree);
-        let data = &self.data;
-        let unused = self.unused_bits;
-        let mut result = Vec::new();
-        let mut input = Vec::new();
-        for i in 0..data.len() * 8 - unused as usize {
-            let indx = i / 8;
-            let bit = (i % 8) as u8;
-            input.push(data[indx] & (1 << bit) != 0);
-            if let Some(char) = tree.decrypt_char(&input) {
-                result.push(char);
-                input.clear();
-            }
-        }
-        result
+    pub fn decrypt(&self) -> Result<Vec<u8>> {
+        HuffmanTree::better_deserialize(&self.tree)?.decode_bits(&self.data, self.unused_bits)
     }
 }
 
@@ -110,46 +83,74 @@ pub struct HuffmanTree {
 }
 
 impl HuffmanTree {
-    pub fn better_serialize(&self) -> Vec<u8> {
+    pub fn better_serialize(&self) -> Result<Vec<u8>> {
         let mut bitbuffer = bitbuffer::BitBuffer::new();
-        self.beter_serialize_rec(&mut bitbuffer);
+        self.beter_serialize_rec(&mut bitbuffer)?;
         bitbuffer.serialize()
     }
 
-    fn beter_serialize_rec(&self, bitbuffer: &mut bitbuffer::BitBuffer) {
-        match self.character {
-            Some(c) => {
+    fn beter_serialize_rec(&self, bitbuffer: &mut bitbuffer::BitBuffer) -> Result<()> {
+        match (self.character, self.children.as_slice()) {
+            (Some(c), _) => {
                 bitbuffer.write_bit(true);
                 bitbuffer.write_byte(c);
             }
-            None => {
+            (None, [left, right]) => {
                 bitbuffer.write_bit(false);
-                self.children[0].beter_serialize_rec(bitbuffer);
-                self.children[1].beter_serialize_rec(bitbuffer);
+                left.beter_serialize_rec(bitbuffer)?;
+                right.beter_serialize_rec(bitbuffer)?;
             }
+            (None, _) => return Err(Error::InvalidParameter("huffman tree node without character needs two children".to_string())),
         }
+        Ok(())
     }
 
-    pub fn better_deserialize(input: &[u8]) -> Self {
-        let mut bitbuffer = bitbuffer::BitBuffer::deserialize(input);
-        Self::better_deserialize_rec(&mut bitbuffer)
+    pub fn better_deserialize(input: &[u8]) -> Result<Self> {
+        let mut bitbuffer = bitbuffer::BitBuffer::deserialize(input)?;
+        Self::better_deserialize_rec(&mut bitbuffer, 0)
     }
 
-    fn better_deserialize_rec(bitbuffer: &mut bitbuffer::BitBuffer) -> Self {
-        if let Some(true) = bitbuffer.read_bit() {
-            Self {
+    fn better_deserialize_rec(bitbuffer: &mut bitbuffer::BitBuffer, depth: usize) -> Result<Self> {
+        if depth > 256 {
+            return Err(Error::Corrupt("huffman tree is too deep".to_string()));
+        }
+        match bitbuffer.read_bit() {
+            Some(true) => Ok(Self {
                 children: vec![],
-                character: bitbuffer.read_byte(),
-            }
-        } else {
-            Self {
+                character: Some(bitbuffer.read_byte().ok_or_else(|| Error::Corrupt("huffman tree is truncated".to_string()))?),
+            }),
+            Some(false) => Ok(Self {
                 children: vec![
-                    Self::better_deserialize_rec(bitbuffer),
-                    Self::better_deserialize_rec(bitbuffer),
+                    Self::better_deserialize_rec(bitbuffer, depth + 1)?,
+                    Self::better_deserialize_rec(bitbuffer, depth + 1)?,
                 ],
                 character: None,
+            }),
+            None => Err(Error::Corrupt("huffman tree is truncated".to_string())),
+        }
+    }
+
+    fn decode_bits(&self, data: &[u8], unused: u8) -> Result<Vec<u8>> {
+        let num_bits = (data.len() * 8).checked_sub(unused as usize)
+            .filter(|_| unused < 8)
+            .ok_or_else(|| Error::Corrupt("huffman data has more unused bits than bits".to_string()))?;
+        let mut result = Vec::new();
+        let mut input = Vec::new();
+        for i in 0..num_bits {
+            let indx = i / 8;
+            let bit = (i % 8) as u8;
+            input.push(data[indx] & (1 << bit) != 0);
+            if let Some(char) = self.decrypt_char(&input) {
+                result.push(char);
+                input.clear();
+            } else if input.len() > 256 {
+                return Err(Error::Corrupt("invalid huffman code".to_string()));
             }
         }
+        if !input.is_empty() {
+            return Err(Error::Corrupt("huffman data ends inside a code".to_string()));
+        }
+        Ok(result)
     }
 
     pub fn from_counts(counts: [u64;256]) -> HuffmanTree {
@@ -170,7 +171,7 @@ impl HuffmanTree {
         pq.pop().unwrap().0
     }
 
-    pub fn build_tree(input: &Vec<u8>) -> HuffmanTree {
+    pub fn build_tree(input: &[u8]) -> HuffmanTree {
         let mut counts = [0u64;256];
 
         for &e in input {
@@ -186,30 +187,32 @@ impl HuffmanTree {
                 if *first {
                     self.children.get(1).and_then(|child| child.decrypt_char(rest))
                 } else {
-                    self.children.get(0).and_then(|child| child.decrypt_char(rest))
+                    self.children.first().and_then(|child| child.decrypt_char(rest))
                 }
             }
             None => self.character,
         }
     }
 
-    fn build_map(&self, current_path: Vec<bool>, map: &mut Vec<Vec<bool>>) {
-        match self.character {
-            Some(c) => {
+    fn build_map(&self, current_path: Vec<bool>, map: &mut Vec<Vec<bool>>) -> Result<()> {
+        match (self.character, self.children.as_slice()) {
+            (Some(c), _) => {
                 map[c as usize] = current_path;
             }
-            None => {
-                self.children[0].build_map({
+            (None, [left, right]) => {
+                left.build_map({
                     let mut path = current_path.clone();
                     path.push(false);
                     path
-                }, map);
-                self.children[1].build_map({
+                }, map)?;
+                right.build_map({
                     let mut path = current_path.clone();
                     path.push(true);
                     path
-                }, map);
+                }, map)?;
             }
+            (None, _) => return Err(Error::InvalidParameter("huffman tree node without character needs two children".to_string())),
         }
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,263 @@
+//! Layouts written by earlier versions of the archive format, converted to the current
+//! one when they are read. Names are decoded as `Name`, which bincode stores exactly
+//! like the `String` older versions used.
+
+use std::time::SystemTime;
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{error::{Error, Result}, file_system::{Archive, Content, FileData}, huffman::{Huffman, HuffmanNoTree, HuffmanTree}, lz77::LZ77, metadata::{Metadata, Timestamp}, name::Name};
+
+/// The first version that keeps file data apart from a central index. Earlier
+/// archives are a single serialized tree right after the header.
+pub(crate) const INDEXED_VERSION: u16 = 6;
+
+/// File data stored in the tree of formats 0 to 5. Format 5 added `Reference`.
+#[derive(Deserialize)]
+enum InlineData {
+    FixedHuffman { data: HuffmanNoTree },
+    Huffman { data: Huffman },
+    LZ77Huffman { data: Huffman, bits: u8 },
+    LZ77 { data: LZ77, bits: u8 },
+    Binary { data: Vec<u8> },
+    Reference { path: Name, hard_link: bool },
+}
+
+/// Format 0, written without a header by the first release. Files did not record
+/// their size either, and the root was the archived directory itself.
+#[derive(Deserialize)]
+enum V0 {
+    File { name: Name, content: InlineData },
+    Directory { name: Name, children: Vec<V0> },
+    Root { name: Name, children: Vec<V0>, tree: Vec<u8> },
+}
+
+/// Format 1, without checksums or metadata.
+#[derive(Deserialize)]
+enum V1 {
+    File { name: Name, size: u64, content: InlineData },
+    Directory { name: Name, children: Vec<V1> },
+    Root { name: Name, children: Vec<V1>, tree: Vec<u8> },
+}
+
+/// Format 2 added a CRC32 to files.
+#[derive(Deserialize)]
+enum V2 {
+    File { name: Name, size: u64, checksum: u32, content: InlineData },
+    Directory { name: Name, children: Vec<V2> },
+    Root { name: Name, children: Vec<V2>, tree: Vec<u8> },
+}
+
+/// Format 3 added metadata to files and directories.
+#[derive(Deserialize)]
+enum V3 {
+    File { name: Name, size: u64, checksum: u32, metadata: Metadata, content: InlineData },
+    Directory { name: Name, metadata: Metadata, children: Vec<V3> },
+    Root { name: Name, children: Vec<V3>, tree: Vec<u8> },
+}
+
+/// Formats 4 and 5 added symbolic links, then references to identical files.
+#[derive(Deserialize)]
+enum V4 {
+    File { name: Name, size: u64, checksum: u32, metadata: Metadata, content: InlineData },
+    Directory { name: Name, metadata: Metadata, children: Vec<V4> },
+    Symlink { name: Name, target: Name },
+    Root { name: Name, children: Vec<V4>, tree: Vec<u8> },
+}
+
+/// Index of formats 6 and 7, from before solid blocks.
+#[derive(Deserialize)]
+enum V6 {
+    File { name: Name, size: u64, checksum: u32, metadata: Metadata, content: Content },
+    Directory { name: Name, metadata: Metadata, children: Vec<V6> },
+    Symlink { name: Name, target: Name },
+    Root { name: Name, children: Vec<V6>, tree: Vec<u8> },
+}
+
+/// Moves the file data out of an inline tree, so it can be read like the payloads
+/// of an indexed archive.
+struct Inline {
+    tree: HuffmanTree,
+    /// Serialized `FileData` of every file, with offsets counted from its start.
+    payloads: Vec<u8>,
+    /// Timestamp given to entries of formats without metadata.
+    now: Timestamp,
+}
+
+impl Inline {
+    /// Stores the data of a file, computing its size and checksum if the format had none.
+    fn file(&mut self, name: Name, size: Option<u64>, checksum: Option<u32>, metadata: Metadata, content: InlineData) -> Result<Archive> {
+        let data = match content {
+            InlineData::Reference { path, hard_link } => {
+                let content = Content::Reference { path, hard_link };
+                return Ok(Archive::File { name, size: size.unwrap_or_default(), checksum: checksum.unwrap_or_default(), metadata, content });
+            },
+            InlineData::FixedHuffman { data } => FileData::FixedHuffman { data },
+            InlineData::Huffman { data } => FileData::Huffman { data },
+            InlineData::LZ77Huffman { data, bits } => FileData::LZ77Huffman { data, bits },
+            InlineData::LZ77 { data, bits } => FileData::LZ77 { data, bits },
+            InlineData::Binary { data } => FileData::Binary { data },
+        };
+        let (size, checksum) = match (size, checksum) {
+            (Some(size), Some(checksum)) => (size, checksum),
+            _ => {
+                let decoded = data.decode(&self.tree)?;
+                (size.unwrap_or(decoded.len() as u64), checksum.unwrap_or_else(|| crc32fast::hash(&decoded)))
+            },
+        };
+        let serialized = bincode::serialize(&data)?;
+        let content = Content::Stored { offset: self.payloads.len() as u64, length: serialized.len() as u64, codec: data.codec() };
+        self.payloads.extend(serialized);
+        Ok(Archive::File { name, size, checksum, metadata, content })
+    }
+
+    /// Metadata for formats that had none: the given mode, owned by root, modified now.
+    fn metadata(&self, mode: u32) -> Metadata {
+        Metadata { mode, modified: self.now, accessed: self.now, uid: 0, gid: 0 }
+    }
+}
+
+/// A tree of one of the inline formats.
+trait InlineEntry: DeserializeOwned {
+    /// The name, children and serialized fixed tree of a `Root`.
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)>;
+    fn convert(self, inline: &mut Inline) -> Result<Archive>;
+}
+
+fn convert_all<T: InlineEntry>(children: Vec<T>, inline: &mut Inline) -> Result<Vec<Archive>> {
+    children.into_iter().map(|child| child.convert(inline)).collect()
+}
+
+impl InlineEntry for V0 {
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)> {
+        match self {
+            V0::Root { name, children, tree } => Some((name.clone(), vec![V0::Directory { name, children }], tree)),
+            _ => None,
+        }
+    }
+
+    fn convert(self, inline: &mut Inline) -> Result<Archive> {
+        Ok(match self {
+            V0::File { name, content } => inline.file(name, None, None, inline.metadata(0o644), content)?,
+            V0::Directory { name, children } => Archive::Directory { name, metadata: inline.metadata(0o755), children: convert_all(children, inline)? },
+            V0::Root { .. } => return Err(Archive::not_root()),
+        })
+    }
+}
+
+impl InlineEntry for V1 {
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)> {
+        match self {
+            V1::Root { name, children, tree } => Some((name, children, tree)),
+            _ => None,
+        }
+    }
+
+    fn convert(self, inline: &mut Inline) -> Result<Archive> {
+        Ok(match self {
+            V1::File { name, size, content } => inline.file(name, Some(size), None, inline.metadata(0o644), content)?,
+            V1::Directory { name, children } => Archive::Directory { name, metadata: inline.metadata(0o755), children: convert_all(children, inline)? },
+            V1::Root { .. } => return Err(Archive::not_root()),
+        })
+    }
+}
+
+impl InlineEntry for V2 {
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)> {
+        match self {
+            V2::Root { name, children, tree } => Some((name, children, tree)),
+            _ => None,
+        }
+    }
+
+    fn convert(self, inline: &mut Inline) -> Result<Archive> {
+        Ok(match self {
+            V2::File { name, size, checksum, content } => inline.file(name, Some(size), Some(checksum), inline.metadata(0o644), content)?,
+            V2::Directory { name, children } => Archive::Directory { name, metadata: inline.metadata(0o755), children: convert_all(children, inline)? },
+            V2::Root { .. } => return Err(Archive::not_root()),
+        })
+    }
+}
+
+impl InlineEntry for V3 {
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)> {
+        match self {
+            V3::Root { name, children, tree } => Some((name, children, tree)),
+            _ => None,
+        }
+    }
+
+    fn convert(self, inline: &mut Inline) -> Result<Archive> {
+        Ok(match self {
+            V3::File { name, size, checksum, metadata, content } => inline.file(name, Some(size), Some(checksum), metadata, content)?,
+            V3::Directory { name, metadata, children } => Archive::Directory { name, metadata, children: convert_all(children, inline)? },
+            V3::Root { .. } => return Err(Archive::not_root()),
+        })
+    }
+}
+
+impl InlineEntry for V4 {
+    fn into_root(self) -> Option<(Name, Vec<Self>, Vec<u8>)> {
+        match self {
+            V4::Root { name, children, tree } => Some((name, children, tree)),
+            _ => None,
+        }
+    }
+
+    fn convert(self, inline: &mut Inline) -> Result<Archive> {
+        Ok(match self {
+            V4::File { name, size, checksum, metadata, content } => inline.file(name, Some(size), Some(checksum), metadata, content)?,
+            V4::Directory { name, metadata, children } => Archive::Directory { name, metadata, children: convert_all(children, inline)? },
+            V4::Symlink { name, target } => Archive::Symlink { name, target },
+            V4::Root { .. } => return Err(Archive::not_root()),
+        })
+    }
+}
+
+impl V6 {
+    fn upgrade(self) -> Archive {
+        match self {
+            V6::File { name, size, checksum, metadata, content } => Archive::File { name, size, checksum, metadata, content },
+            V6::Directory { name, metadata, children } => Archive::Directory { name, metadata, children: children.into_iter().map(V6::upgrade).collect() },
+            V6::Symlink { name, target } => Archive::Symlink { name, target },
+            V6::Root { name, children, tree } => Archive::Root { name, children: children.into_iter().map(V6::upgrade).collect(), tree, blocks: Vec::new() },
+        }
+    }
+}
+
+fn read_inline_as<T: InlineEntry>(data: &[u8]) -> Result<(Archive, Vec<u8>)> {
+    let (name, children, tree) = bincode::deserialize::<T>(data)?.into_root()
+        .ok_or_else(|| Error::Corrupt("archive does not start with a root entry".to_string()))?;
+    let mut inline = Inline {
+        tree: Archive::Root { name: Name::default(), children: Vec::new(), tree: tree.clone(), blocks: Vec::new() }.fixed_tree()?,
+        payloads: Vec::new(),
+        now: SystemTime::now().into(),
+    };
+    let children = convert_all(children, &mut inline)?;
+    Ok((Archive::Root { name, children, tree, blocks: Vec::new() }, inline.payloads))
+}
+
+/// Converts `data`, everything after the header of an archive of format `version`
+/// below `INDEXED_VERSION`, into an index and the payloads its entries point to.
+/// Format 0 has no header, so anything that does not decode as one is not an archive.
+pub(crate) fn read_inline(version: u16, data: &[u8]) -> Result<(Archive, Vec<u8>)> {
+    match version {
+        0 => read_inline_as::<V0>(data).map_err(|error| match error {
+            Error::Corrupt(_) => Error::Corrupt("not a tmy archive".to_string()),
+            error => error,
+        }),
+        1 => read_inline_as::<V1>(data),
+        2 => read_inline_as::<V2>(data),
+        3 => read_inline_as::<V3>(data),
+        4 | 5 => read_inline_as::<V4>(data),
+        _ => Err(Error::Corrupt(format!("unknown archive format {}", version))),
+    }
+}
+
+/// Decodes the index of an archive of format `version`.
+pub(crate) fn read_index(version: u16, index: &[u8]) -> Result<Archive> {
+    Ok(match version {
+        6 | 7 => bincode::deserialize::<V6>(index)?.upgrade(),
+        _ => bincode::deserialize(index)?,
+    })
+}
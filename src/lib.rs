@@ -0,0 +1,39 @@
+//! Huffman and LZ77 based compression of single streams and whole directory
+//! trees into `.tmy` archives.
+
+pub mod bitbuffer;
+pub mod compress;
+mod crypto;
+mod dedup;
+pub mod edit;
+pub mod error;
+pub mod extract;
+pub mod file_system;
+mod filter;
+pub mod header;
+pub mod huffman;
+mod legacy;
+pub mod lz77;
+pub mod metadata;
+pub mod name;
+pub mod progress;
+pub mod reader;
+mod solid;
+pub mod stream;
+pub mod volumes;
+pub mod writer;
+// mod ukkonen;
+
+pub use bitbuffer::BitBuffer;
+pub use compress::CompressOptions;
+pub use crypto::Encryption;
+pub use error::{Error, Result};
+pub use extract::{ExtractOptions, ExtractReport, OverwritePolicy};
+pub use file_system::{Archive, Content, FileData};
+pub use huffman::{Huffman, HuffmanNoTree, HuffmanTree};
+pub use lz77::LZ77;
+pub use metadata::Metadata;
+pub use name::Name;
+pub use progress::{NoProgress, Progress};
+pub use reader::ArchiveReader;
+pub use writer::ArchiveWriter;
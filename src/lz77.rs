@@ -1,6 +1,6 @@
 use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use crate::bitbuffer::{self, BitBuffer};
+use crate::{bitbuffer::{self, BitBuffer}, error::{Error, Result}};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct LZ77 {
@@ -8,12 +8,19 @@ pub struct LZ77 {
 }
 
 impl LZ77 {
-    pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(&self).unwrap()
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&self)?)
     }
     
-    pub fn deserialize(input: &[u8]) -> Self {
-        bincode::deserialize(input).unwrap()
+    pub fn deserialize(input: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(input)?)
+    }
+
+    fn check_bits(bits: u8) -> Result<()> {
+        if !(2..=24).contains(&bits) {
+            return Err(Error::InvalidParameter(format!("LZ77 window of {} bits is outside 2..=24", bits)));
+        }
+        Ok(())
     }
 
     fn lpc(input: &[u8], i: usize, j: usize) -> usize {
@@ -27,7 +34,7 @@ impl LZ77 {
         k
     }
 
-    pub fn fast_encode(input: &[u8], bits: u8) -> BitBuffer {
+    fn fast_encode(input: &[u8], bits: u8) -> BitBuffer {
         let n = input.len();
 
         let mut suffix_array = (0..=n).collect::<Vec<usize>>();
@@ -49,7 +56,6 @@ impl LZ77 {
             psv[i] = j;
         }
         psv = psv.into_iter().map(|i| if i == usize::MAX {0} else {i}).collect::<Vec<_>>();
-        nsv = nsv.into_iter().map(|i| i).collect::<Vec<_>>();
 
         let mut factors = Vec::new();
         let mut k = 0;
@@ -68,11 +74,11 @@ impl LZ77 {
             if l == 0 {
                 acc.write_bits(0, lenght_size);
                 acc.write_byte(c);
-            } else if l < max_lenght as usize {
+            } else if l < max_lenght {
                 acc.write_bits(l as u32, lenght_size);
                 acc.write_bits(p as u32, bits);
             } else {
-                while l >= max_lenght as usize{
+                while l >= max_lenght {
                     acc.write_bits(u32::MAX, lenght_size);
                     acc.write_bits(p as u32, bits);
                     p += max_lenght;
@@ -88,22 +94,22 @@ impl LZ77 {
     }
 
     fn lenght_size(bits: u8) -> u8 {
-        (bits - 2).min(6).max(2)
+        (bits - 2).clamp(2, 6)
     }
 
-    fn decode_chunk(factors: &Vec<(usize, usize, u8)>) -> Vec<u8> {
+    fn decode_chunk(factors: &[(usize, usize, u8)]) -> Result<Vec<u8>> {
         let mut decoded = Vec::new();
         for (p,l,c) in factors {
             if *l == 0 {
                 decoded.push(*c);
             } else {
                 for i in 0..*l {
-                    let c = decoded[p + i];
+                    let c = *decoded.get(p + i).ok_or_else(|| Error::Corrupt("LZ77 reference points past decoded data".to_string()))?;
                     decoded.push(c);
                 }
             }
         }
-        decoded
+        Ok(decoded)
     }
 
     fn lz_factor(i:usize, psv: usize, nsv: usize, x: &[u8]) -> (usize, usize, u8, usize) {
@@ -121,39 +127,42 @@ impl LZ77 {
         (p, l, *e, i + l.max(1))
     }
 
-    pub fn encode(input: &[u8], bits: u8) -> LZ77 {
+    pub fn encode(input: &[u8], bits: u8) -> Result<LZ77> {
+        Self::check_bits(bits)?;
         let n = input.len();
         let chunk_size = 2usize.pow(bits as u32) - 1;
-        let num_chunks = n / chunk_size + if n % chunk_size == 0 {0} else {1};
+        let num_chunks = n / chunk_size + if n.is_multiple_of(chunk_size) {0} else {1};
 
         let data = (0..num_chunks).into_par_iter()
             .map(|i| {
                 let start = i * chunk_size;
                 let end = usize::min((i + 1) * chunk_size, n);
                 let chunk = &input[start..end];
-                let factors = LZ77::fast_encode(chunk, bits);
-                factors
+                LZ77::fast_encode(chunk, bits)
             })
             .collect::<Vec<_>>();
 
-        LZ77 {
+        Ok(LZ77 {
             bitbuffers: data,
-        }
+        })
     }
 
-    pub fn decode(&mut self, bits: u8) -> Vec<u8> {
+    pub fn decode(&mut self, bits: u8) -> Result<Vec<u8>> {
+        Self::check_bits(bits)?;
         let lenght_size = Self::lenght_size(bits);
-        self.bitbuffers.par_iter_mut().flat_map(|chunk| {
+        let truncated = || Error::Corrupt("LZ77 factor is truncated".to_string());
+        let chunks = self.bitbuffers.par_iter_mut().map(|chunk| {
             let mut factors = Vec::new();
             while let Some(l) = chunk.read_bits(lenght_size) {
                 if l == 0 {
-                    factors.push((0, 0, chunk.read_byte().unwrap()));
+                    factors.push((0, 0, chunk.read_byte().ok_or_else(truncated)?));
                 } else {
-                    factors.push((chunk.read_bits(bits).unwrap() as usize, l as usize, 0));              
+                    factors.push((chunk.read_bits(bits).ok_or_else(truncated)? as usize, l as usize, 0));              
                 }
             }
             LZ77::decode_chunk(&factors)
-        }).collect::<Vec<_>>()
+        }).collect::<Result<Vec<_>>>()?;
+        Ok(chunks.concat())
     }
 
 }
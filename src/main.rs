@@ -1,24 +1,267 @@
-mod huffman;
-mod file_system;
-mod lz77;
-pub mod bitbuffer;
-// mod ukkonen;
-use std::fs;
+mod cli;
+mod progress_bar;
+
+use std::{any::Any, env, fs, io::{self, BufWriter, Write}, panic, path::{Path, PathBuf}, process};
+
+use clap::Parser;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use cli::{Cli, Command};
+use progress_bar::ProgressBar;
+use huffman::{edit, error::PathContext, stream, volumes::{self, VolumeWriter}, Archive, ArchiveReader, ArchiveWriter, CompressOptions, Content, Encryption, ExtractOptions, Error, ExtractReport, NoProgress, Result};
 
 fn main() {
-    let args = std::env::args().collect::<Vec<_>>();
-
-    if let Some(path) = args.get(1) {
-        println!("Decoding archive {}", path);
-        let data = fs::read(path).unwrap();
-        let archive = file_system::Archive::deserialize(&data);
-        archive.write_directory(".");
-        println!("Decoding complete")
-    } else {
-        println!("Encoding current directory");
-        let archive = file_system::Archive::read_directory(".");
-        let serialized = archive.serialize();
-        fs::write(format!("./{}.tmy", archive.get_name()), &serialized).unwrap();
-        println!("Encoding complete\noutput file: {}.tmy", archive.get_name());
+    match run(Cli::parse()) {
+        Ok(true) => {},
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(1);
+        },
+    }
+}
+
+fn run(cli: Cli) -> Result<bool> {
+    if cli.compress || cli.decompress {
+        let (stdin, stdout) = (io::stdin().lock(), BufWriter::new(io::stdout().lock()));
+        match cli.compress {
+            true => stream::compress(stdin, stdout)?,
+            false => stream::decompress(stdin, stdout)?,
+        }
+        return Ok(true);
+    }
+
+    let Some(command) = cli.command else {
+        return Ok(true);
+    };
+    match command {
+        Command::Compress { inputs, output, input, volume_size, encrypt, encrypt_names } => {
+            let encryption = match encrypt || encrypt_names {
+                true => Some(Encryption { passphrase: password(true)?, encrypt_index: encrypt_names }),
+                false => None,
+            };
+            println!("Encoding {}", inputs.iter().map(|input| input.display().to_string()).collect::<Vec<_>>().join(", "));
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let mut output = Archive::archive_name(&inputs)?.to_os_string()?;
+                    output.push(".tmy");
+                    PathBuf::from(output)
+                },
+            };
+            if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).at(parent)?;
+            }
+            let options = CompressOptions::from(input);
+            if let Err(error) = compress(&inputs, &output, &options, encryption.as_ref(), volume_size) {
+                let _ = fs::remove_file(&output);
+                let _ = volumes::remove_volumes(&output);
+                return Err(error);
+            }
+            match volume_size {
+                Some(_) => println!("Encoding complete\noutput files: {}, ...", volumes::volume_path(&output, 1).display()),
+                None => println!("Encoding complete\noutput file: {}", output.display()),
+            }
+        },
+        Command::Extract { archive: archive_path, patterns, output, overwrite, same_owner } => {
+            println!("Decoding archive {}", archive_path.display());
+            let reader = open(&archive_path, true)?;
+            let selected;
+            let archive = match patterns.is_empty() {
+                true => reader.index(),
+                false => {
+                    selected = reader.index().select(&patterns)?;
+                    if selected.is_empty() {
+                        println!("No entries match the given patterns");
+                        return Ok(false);
+                    }
+                    &selected
+                },
+            };
+            let options = ExtractOptions {
+                overwrite,
+                restore_ownership: same_owner,
+            };
+            let progress = ProgressBar::new();
+            let report = archive.write_directory(&reader, &output, &options, &progress)?;
+            progress.finish();
+            print_report(&report);
+            println!("Decoding complete")
+        },
+        Command::Add { archive, inputs, input } => add(&archive, &inputs, &input.into(), false)?,
+        Command::Update { archive, inputs, input } => add(&archive, &inputs, &input.into(), true)?,
+        Command::Delete { archive, patterns } => {
+            let removed = with_password(|passphrase| edit::delete(&archive, &patterns, passphrase, &NoProgress))?;
+            println!("Removed {} entries:", removed.len());
+            removed.iter().for_each(|path| println!("  {}", path));
+        },
+        Command::List { archive } => list(open(&archive, false)?.index()),
+        Command::Test { archive } => return test(&archive),
+    }
+    Ok(true)
+}
+
+/// The password in HUFFMAN_PASSWORD, or one asked for on the terminal, twice if `confirm`.
+fn password(confirm: bool) -> Result<String> {
+    let password = match env::var("HUFFMAN_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => {
+            let prompt = |prompt| rpassword::prompt_password(prompt)
+                .map_err(|error| Error::InvalidParameter(format!("cannot ask for a password ({}), set HUFFMAN_PASSWORD instead", error)));
+            let password = prompt("Password: ")?;
+            if confirm && prompt("Repeat password: ")? != password {
+                return Err(Error::InvalidParameter("passwords do not match".to_string()));
+            }
+            password
+        },
+    };
+    match password.is_empty() {
+        true => Err(Error::InvalidParameter("password must not be empty".to_string())),
+        false => Ok(password),
+    }
+}
+
+/// Runs `f` without a password, and again with one if the archive turns out to be encrypted.
+fn with_password<T>(f: impl Fn(Option<&str>) -> Result<T>) -> Result<T> {
+    match f(None) {
+        Err(Error::PasswordRequired) => f(Some(&password(false)?)),
+        result => result,
+    }
+}
+
+/// Opens the archive at `path`, asking for the password of an encrypted archive when its
+/// index is encrypted or `read_data` is set.
+fn open(path: &Path, read_data: bool) -> Result<ArchiveReader> {
+    with_password(|passphrase| {
+        let reader = ArchiveReader::open(path, passphrase)?;
+        match read_data && reader.is_encrypted() && passphrase.is_none() {
+            true => Err(Error::PasswordRequired),
+            false => Ok(reader),
+        }
+    })
+}
+
+fn compress(inputs: &[PathBuf], output: &Path, options: &CompressOptions, encryption: Option<&Encryption>, volume_size: Option<u64>) -> Result<()> {
+    let output: Box<dyn Write + Send> = match volume_size {
+        Some(volume_size) => Box::new(VolumeWriter::create(output, volume_size)?),
+        None => Box::new(BufWriter::new(fs::File::create(output).at(output)?)),
+    };
+    let writer = ArchiveWriter::new(output, encryption)?;
+    let progress = ProgressBar::new();
+    let archive = Archive::read_paths(inputs, options, &writer, &progress)?;
+    progress.finish();
+    writer.finish(&archive)
+}
+
+fn add(archive: &Path, inputs: &[PathBuf], options: &CompressOptions, replace: bool) -> Result<()> {
+    println!("Adding {} to {}", inputs.iter().map(|input| input.display().to_string()).collect::<Vec<_>>().join(", "), archive.display());
+    let progress = ProgressBar::new();
+    match replace {
+        true => with_password(|passphrase| edit::update(archive, inputs, options, passphrase, &progress))?,
+        false => with_password(|passphrase| edit::add(archive, inputs, options, passphrase, &progress))?,
+    }
+    progress.finish();
+    println!("Archive updated");
+    Ok(())
+}
+
+fn print_report(report: &ExtractReport) {
+    if !report.skipped.is_empty() {
+        println!("Skipped {} existing entries:", report.skipped.len());
+        report.skipped.iter().for_each(|path| println!("  {}", path.display()));
+    }
+    if !report.replaced.is_empty() {
+        println!("Replaced {} existing entries:", report.replaced.len());
+        report.replaced.iter().for_each(|path| println!("  {}", path.display()));
+    }
+    if !report.renamed.is_empty() {
+        println!("Renamed {} entries that already existed:", report.renamed.len());
+        report.renamed.iter().for_each(|(from, to)| println!("  {} -> {}", from.display(), to.display()));
+    }
+    if !report.refused.is_empty() {
+        println!("Refused {} unsafe entries:", report.refused.len());
+        report.refused.iter().for_each(|(path, reason)| println!("  {}: {}", path, reason));
+    }
+}
+
+fn ratio(stored: u64, original: u64) -> String {
+    match original {
+        0 => "-".to_string(),
+        _ => format!("{:.1}%", stored as f64 / original as f64 * 100.0),
+    }
+}
+
+fn list(archive: &Archive) {
+    let mut entries = Vec::new();
+    archive.walk(&mut |path, entry| entries.push((path.to_string(), entry)));
+
+    println!("{:>12} {:>12} {:>7}  {:<22} Path", "Original", "Stored", "Ratio", "Codec");
+    let (mut files, mut original, mut stored, mut solid) = (0, 0, 0, 0);
+    for (path, entry) in entries {
+        match entry {
+            Archive::File { size, content, .. } => {
+                let stored_size = content.stored_size();
+                let same_as = match content {
+                    Content::Reference { path, .. } => format!(" = {}", path),
+                    _ => String::new(),
+                };
+                println!("{:>12} {:>12} {:>7}  {:<22} {}{}", size, stored_size, ratio(stored_size, *size), content.codec(), path, same_as);
+                files += 1;
+                original += size;
+                stored += stored_size;
+                if let Content::Solid { .. } = content {
+                    solid += size;
+                }
+            },
+            Archive::Symlink { target, .. } => println!("{:>12} {:>12} {:>7}  {:<22} {} -> {}", "", "", "", "Symlink", path, target),
+            _ => println!("{:>12} {:>12} {:>7}  {:<22} {}/", "", "", "", "", path),
+        }
+    }
+    if let Archive::Root { blocks, .. } = archive {
+        if !blocks.is_empty() {
+            let blocks_size = blocks.iter().map(Content::stored_size).sum::<u64>();
+            println!("{:>12} {:>12} {:>7}  {:<22} {} solid blocks", solid, blocks_size, ratio(blocks_size, solid), "", blocks.len());
+            stored += blocks_size;
+        }
+    }
+    println!("{:>12} {:>12} {:>7}  {} files", original, stored, ratio(stored, original), files);
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown error".to_string())
+}
+
+fn test(path: &Path) -> Result<bool> {
+    let reader = match open(path, true) {
+        Ok(reader) => reader,
+        Err(error @ (Error::Corrupt(_) | Error::ChecksumMismatch(_) | Error::UnsupportedVersion { .. })) => {
+            println!("Archive is corrupt: {}", error);
+            return Ok(false);
+        },
+        Err(error) => return Err(error),
+    };
+
+    let mut files = Vec::new();
+    reader.index().walk(&mut |path, entry| if let Archive::File { .. } = entry {
+        files.push((path.clone(), entry));
+    });
+
+    panic::set_hook(Box::new(|_| {}));
+    let failures = files.par_iter()
+        .filter_map(|(path, entry)| {
+            match panic::catch_unwind(|| reader.decode_entry(path, entry)) {
+                Ok(Ok(_)) => None,
+                Ok(Err(error)) => Some((path, error.to_string())),
+                Err(payload) => Some((path, panic_message(payload))),
+            }
+        })
+        .collect::<Vec<_>>();
+    let _ = panic::take_hook();
+
+    for (path, error) in &failures {
+        println!("FAILED {}: {}", path, error);
     }
+    println!("{} of {} files OK", files.len() - failures.len(), files.len());
+    Ok(failures.is_empty())
 }
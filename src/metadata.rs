@@ -0,0 +1,100 @@
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PathContext, Result};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32,
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Timestamp { secs: after.as_secs() as i64, nanos: after.subsec_nanos() },
+            Err(error) => {
+                let before = error.duration();
+                match before.subsec_nanos() {
+                    0 => Timestamp { secs: -(before.as_secs() as i64), nanos: 0 },
+                    nanos => Timestamp { secs: -(before.as_secs() as i64) - 1, nanos: 1_000_000_000 - nanos },
+                }
+            },
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(time: Timestamp) -> Self {
+        match time.secs {
+            0.. => UNIX_EPOCH + Duration::new(time.secs as u64, time.nanos),
+            _ => UNIX_EPOCH - Duration::from_secs(time.secs.unsigned_abs()) + Duration::from_nanos(time.nanos as u64),
+        }
+    }
+}
+
+/// Permissions, timestamps and ownership of an archived file or directory.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Metadata {
+    pub mode: u32,
+    pub modified: Timestamp,
+    pub accessed: Timestamp,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Metadata {
+    #[cfg(unix)]
+    pub fn read(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path).at(path)?;
+        Ok(Metadata {
+            mode: metadata.mode() & 0o7777,
+            modified: metadata.modified().at(path)?.into(),
+            accessed: metadata.accessed().at(path)?.into(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path).at(path)?;
+        Ok(Metadata {
+            mode: if metadata.permissions().readonly() { 0o444 } else { 0o644 },
+            modified: metadata.modified().at(path)?.into(),
+            accessed: metadata.accessed().at(path)?.into(),
+            uid: 0,
+            gid: 0,
+        })
+    }
+
+    /// Restores timestamps, ownership (if requested) and permissions, in that order
+    /// so that a read-only mode does not get in the way of the other two.
+    pub fn apply(&self, path: &Path, restore_ownership: bool) -> Result<()> {
+        let times = fs::FileTimes::new()
+            .set_modified(self.modified.into())
+            .set_accessed(self.accessed.into());
+        fs::File::open(path).and_then(|file| file.set_times(times)).at(path)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if restore_ownership {
+                std::os::unix::fs::chown(path, Some(self.uid), Some(self.gid)).at(path)?;
+            }
+            fs::set_permissions(path, fs::Permissions::from_mode(self.mode)).at(path)?;
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = restore_ownership;
+            let mut permissions = fs::metadata(path).at(path)?.permissions();
+            permissions.set_readonly(self.mode & 0o200 == 0);
+            fs::set_permissions(path, permissions).at(path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::{ffi::{OsStr, OsString}, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// A file name, link target or `/` separated path inside an archive, kept as the
+/// raw bytes the operating system gave us. Displayed lossily as UTF-8.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Default, PartialOrd, Ord)]
+pub struct Name(pub Vec<u8>);
+
+impl Name {
+    #[cfg(unix)]
+    pub fn from_os_str(name: &OsStr) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        Ok(Name(name.as_bytes().to_vec()))
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_os_str(name: &OsStr) -> Result<Self> {
+        name.to_str()
+            .map(Name::from)
+            .ok_or_else(|| crate::Error::InvalidParameter(format!("{} is not valid UTF-8", name.to_string_lossy())))
+    }
+
+    #[cfg(unix)]
+    pub fn to_os_string(&self) -> Result<OsString> {
+        use std::os::unix::ffi::OsStrExt;
+
+        Ok(OsStr::from_bytes(&self.0).to_os_string())
+    }
+
+    #[cfg(not(unix))]
+    pub fn to_os_string(&self) -> Result<OsString> {
+        std::str::from_utf8(&self.0)
+            .map(OsString::from)
+            .map_err(|_| crate::Error::InvalidParameter(format!("{} is not valid UTF-8 and cannot be used on this platform", self)))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Appends `name` as a new path component.
+    pub fn join(&self, name: &Name) -> Name {
+        if self.is_empty() {
+            return name.clone();
+        }
+        let mut path = self.0.clone();
+        path.push(b'/');
+        path.extend_from_slice(&name.0);
+        Name(path)
+    }
+
+    pub fn components(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.split(|&byte| byte == b'/')
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Name(name.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
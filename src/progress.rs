@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Receives the number of files and bytes processed so far. Implementations are
+/// called from rayon worker threads, so updates can arrive slightly out of order.
+pub trait Progress: Sync {
+    fn update(&self, files: u64, bytes: u64, total_files: u64, total_bytes: u64);
+}
+
+impl<F: Fn(u64, u64, u64, u64) + Sync> Progress for F {
+    fn update(&self, files: u64, bytes: u64, total_files: u64, total_bytes: u64) {
+        self(files, bytes, total_files, total_bytes)
+    }
+}
+
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn update(&self, _: u64, _: u64, _: u64, _: u64) {}
+}
+
+pub(crate) struct Tracker<'a> {
+    progress: &'a dyn Progress,
+    files: AtomicU64,
+    bytes: AtomicU64,
+    total_files: u64,
+    total_bytes: u64,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn new(progress: &'a dyn Progress, total_files: u64, total_bytes: u64) -> Self {
+        progress.update(0, 0, total_files, total_bytes);
+        Tracker {
+            progress,
+            files: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            total_files,
+            total_bytes,
+        }
+    }
+
+    pub(crate) fn file_done(&self, bytes: u64) {
+        self.advance(1, bytes);
+    }
+
+    pub(crate) fn advance(&self, files: u64, bytes: u64) {
+        let files = self.files.fetch_add(files, Ordering::Relaxed) + files;
+        let bytes = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.progress.update(files, bytes, self.total_files, self.total_bytes);
+    }
+}
@@ -0,0 +1,60 @@
+use std::{io::{self, IsTerminal, Write}, sync::Mutex};
+
+use huffman::Progress;
+
+const WIDTH: usize = 30;
+
+/// Draws a single line progress bar on stderr when it is a terminal.
+pub struct ProgressBar {
+    last: Mutex<Option<(u64, u64)>>,
+    enabled: bool,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        ProgressBar {
+            last: Mutex::new(None),
+            enabled: io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn finish(&self) {
+        if self.enabled && self.last.lock().unwrap().is_some() {
+            eprintln!();
+        }
+    }
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+impl Progress for ProgressBar {
+    fn update(&self, files: u64, bytes: u64, total_files: u64, total_bytes: u64) {
+        if !self.enabled {
+            return;
+        }
+        let permille = match total_bytes {
+            0 => 1000,
+            _ => bytes * 1000 / total_bytes,
+        };
+        let mut last = self.last.lock().unwrap();
+        if last.is_some_and(|(last_files, last_permille)| files <= last_files || permille < last_permille) {
+            return;
+        }
+        *last = Some((files, permille));
+
+        let filled = permille as usize * WIDTH / 1000;
+        eprint!(
+            "\r[{}{}] {:>5.1}% {}/{} files {:.1}/{:.1} MiB",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            permille as f64 / 10.0,
+            files,
+            total_files,
+            megabytes(bytes),
+            megabytes(total_bytes),
+        );
+        let _ = io::stderr().flush();
+    }
+}
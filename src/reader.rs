@@ -0,0 +1,210 @@
+use std::{collections::VecDeque, fs::File, io::{self, Cursor, Read, Seek, SeekFrom}, path::Path, sync::{Arc, Mutex}};
+
+use crate::{crypto::{self, Cipher, Encryption, Purpose}, error::{Error, PathContext, Result}, file_system::{Archive, Content, FileData}, header::{self, Header}, huffman::HuffmanTree, legacy, name::Name, volumes::{self, VolumeReader}};
+
+/// Anything an archive can be read from.
+pub trait Source: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Source for T {}
+
+/// An archive whose index has been loaded. File data is only read from the
+/// source when an entry is decoded.
+pub struct ArchiveReader {
+    source: Mutex<Box<dyn Source>>,
+    index: Archive,
+    tree: HuffmanTree,
+    index_offset: u64,
+    flags: u32,
+    /// The key of an encrypted archive, if it was opened with a passphrase.
+    cipher: Option<(Cipher, String)>,
+    /// Recently decoded solid blocks, oldest first.
+    blocks: Mutex<VecDeque<(u64, Arc<Vec<u8>>)>>,
+}
+
+fn read_exact(source: &mut dyn Source, buffer: &mut [u8]) -> Result<()> {
+    source.read_exact(buffer).map_err(|error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => Error::Corrupt("archive is truncated".to_string()),
+        _ => Error::Io(error),
+    })
+}
+
+impl ArchiveReader {
+    /// Opens a single file archive, or the volumes of a split one.
+    pub fn open(path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        match volumes::split_base(path) {
+            Some(base) => Self::new(Box::new(VolumeReader::open(&base)?), passphrase),
+            None => Self::new(Box::new(File::open(path).at(path)?), passphrase),
+        }
+    }
+
+    /// Loads the index of the archive in `source`. An encrypted archive whose index is not
+    /// encrypted can be opened without `passphrase`, but none of its file data can be read.
+    pub fn new(mut source: Box<dyn Source>, passphrase: Option<&str>) -> Result<Self> {
+        let mut magic = [0; 4];
+        let headerless = match source.read_exact(&mut magic) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => false,
+            result => {
+                result?;
+                magic != header::ARCHIVE_MAGIC
+            },
+        };
+        source.seek(SeekFrom::Start(0))?;
+        if headerless {
+            return Self::from_inline(0, source);
+        }
+        let Header { version, flags, .. } = Header::read(header::ARCHIVE_MAGIC, &mut source)?;
+        if version < legacy::INDEXED_VERSION {
+            return Self::from_inline(version, source);
+        }
+        if flags & header::FLAG_ENCRYPTED_INDEX != 0 && flags & header::FLAG_ENCRYPTED == 0 {
+            return Err(Error::Corrupt("archive has an encrypted index but no key".to_string()));
+        }
+        let data_start = match flags & header::FLAG_ENCRYPTED {
+            0 => Header::LEN as u64,
+            _ => (Header::LEN + Cipher::KEY_BLOCK_LEN) as u64,
+        };
+        let end = source.seek(SeekFrom::End(0))?;
+        if end < data_start + 8 {
+            return Err(Error::Corrupt("archive is truncated".to_string()));
+        }
+        let cipher = match (flags & header::FLAG_ENCRYPTED, passphrase) {
+            (0, _) => None,
+            (_, None) if flags & header::FLAG_ENCRYPTED_INDEX != 0 => return Err(Error::PasswordRequired),
+            (_, None) => None,
+            (_, Some(passphrase)) => {
+                let mut key_block = [0; Cipher::KEY_BLOCK_LEN];
+                source.seek(SeekFrom::Start(Header::LEN as u64))?;
+                read_exact(&mut source, &mut key_block)?;
+                Some((Cipher::open(passphrase, &key_block)?, passphrase.to_string()))
+            },
+        };
+        let mut index_offset = [0; 8];
+        source.seek(SeekFrom::Start(end - 8))?;
+        read_exact(&mut source, &mut index_offset)?;
+        let index_offset = u64::from_le_bytes(index_offset);
+        if index_offset < data_start || index_offset > end - 8 {
+            return Err(Error::Corrupt("index offset is out of range".to_string()));
+        }
+
+        let mut index = vec![0; (end - 8 - index_offset) as usize];
+        source.seek(SeekFrom::Start(index_offset))?;
+        read_exact(&mut source, &mut index)?;
+        if flags & header::FLAG_ENCRYPTED_INDEX != 0 {
+            if let Some((cipher, _)) = &cipher {
+                index = cipher.decrypt(Purpose::Index, index_offset, &index)?;
+            }
+        } else if flags & header::FLAG_ENCRYPTED != 0 {
+            let tag = index.split_off(index.len().checked_sub(crypto::TAG_LEN).ok_or_else(|| Error::Corrupt("index is truncated".to_string()))?);
+            if let Some((cipher, _)) = &cipher {
+                cipher.verify(Purpose::Index, index_offset, &index, &tag)?;
+            }
+        }
+        let index = match legacy::read_index(version, &index)? {
+            index @ Archive::Root { .. } => index,
+            _ => return Err(Error::Corrupt("archive does not start with a root entry".to_string())),
+        };
+        let tree = index.fixed_tree()?;
+        Ok(ArchiveReader { source: Mutex::new(source), index, tree, index_offset, flags, cipher, blocks: Mutex::new(VecDeque::new()) })
+    }
+
+    /// Loads an archive of a format that kept file data inside the tree, moving the data
+    /// into memory where it is read like the payloads of later formats.
+    fn from_inline(version: u16, mut source: Box<dyn Source>) -> Result<Self> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+        let (index, payloads) = legacy::read_inline(version, &data)?;
+        let mut stored = vec![0; Header::LEN];
+        stored.extend(payloads);
+        let tree = index.fixed_tree()?;
+        let index_offset = stored.len() as u64;
+        Ok(ArchiveReader { source: Mutex::new(Box::new(Cursor::new(stored))), index, tree, index_offset, flags: 0, cipher: None, blocks: Mutex::new(VecDeque::new()) })
+    }
+
+    pub fn index(&self) -> &Archive {
+        &self.index
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & header::FLAG_ENCRYPTED != 0
+    }
+
+    /// How to encrypt a new version of this archive the same way.
+    pub(crate) fn encryption(&self) -> Result<Option<Encryption>> {
+        match &self.cipher {
+            Some((_, passphrase)) => Ok(Some(Encryption {
+                passphrase: passphrase.clone(),
+                encrypt_index: self.flags & header::FLAG_ENCRYPTED_INDEX != 0,
+            })),
+            None if self.is_encrypted() => Err(Error::PasswordRequired),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the serialized `FileData` stored at `offset`, decrypting it if needed.
+    pub(crate) fn read_raw(&self, offset: u64, length: u64) -> Result<Vec<u8>> {
+        if self.is_encrypted() && self.cipher.is_none() {
+            return Err(Error::PasswordRequired);
+        }
+        let start = Header::LEN as u64 + offset;
+        if start.checked_add(length).is_none_or(|end| end > self.index_offset) {
+            return Err(Error::Corrupt("entry data is out of range".to_string()));
+        }
+        let mut payload = vec![0; length as usize];
+        {
+            let mut source = self.source.lock().unwrap();
+            source.seek(SeekFrom::Start(start))?;
+            read_exact(&mut *source, &mut payload)?;
+        }
+        match &self.cipher {
+            Some((cipher, _)) => cipher.decrypt(Purpose::Payload, offset, &payload),
+            None => Ok(payload),
+        }
+    }
+
+    fn read_payload(&self, offset: u64, length: u64) -> Result<FileData> {
+        Ok(bincode::deserialize(&self.read_raw(offset, length)?)?)
+    }
+
+    /// Decodes solid block `block`, or takes it from the blocks decoded recently. The files
+    /// of a block are usually read close together, so each block is decoded about once.
+    fn decoded_block(&self, block: u64) -> Result<Arc<Vec<u8>>> {
+        if let Some((_, decoded)) = self.blocks.lock().unwrap().iter().find(|(number, _)| *number == block) {
+            return Ok(decoded.clone());
+        }
+        let Content::Stored { offset, length, .. } = self.index.block(block)? else {
+            return Err(Error::Corrupt(format!("solid block {} is missing", block)));
+        };
+        let decoded = Arc::new(self.read_payload(*offset, *length)?.decode(&self.tree)?);
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.push_back((block, decoded.clone()));
+        if blocks.len() > 2 * rayon::current_num_threads() {
+            blocks.pop_front();
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes the `File` entry `entry` found at `path` and checks its size and
+    /// checksum, naming `path` if they do not match. Encrypted archives have no
+    /// checksums, their data is authenticated when it is decrypted instead.
+    pub fn decode_entry(&self, path: &Name, entry: &Archive) -> Result<Vec<u8>> {
+        let Archive::File { size, checksum, .. } = entry else {
+            return Err(Error::InvalidParameter(format!("{} is not a file", path)));
+        };
+        let decoded = match self.index.resolve(path, entry)? {
+            Content::Stored { offset, length, .. } => self.read_payload(*offset, *length)?.decode(&self.tree)?,
+            Content::Solid { block, start } => {
+                let block = self.decoded_block(*block)?;
+                let range = usize::try_from(*start).ok()
+                    .and_then(|start| Some(start..start.checked_add(usize::try_from(*size).ok()?)?))
+                    .filter(|range| range.end <= block.len())
+                    .ok_or_else(|| Error::Corrupt(format!("{} is out of range of its solid block", path)))?;
+                block[range].to_vec()
+            },
+            Content::Reference { .. } => return Err(Error::Corrupt(format!("{} does not refer to stored data", path))),
+        };
+        if decoded.len() as u64 != *size || (!self.is_encrypted() && crc32fast::hash(&decoded) != *checksum) {
+            return Err(Error::ChecksumMismatch(path.to_string()));
+        }
+        Ok(decoded)
+    }
+}
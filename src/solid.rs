@@ -0,0 +1,88 @@
+use std::{collections::HashMap, mem, sync::Mutex};
+
+use crate::{error::{Error, Result}, file_system::Content, reader::ArchiveReader, writer::ArchiveWriter};
+
+/// The number of a block and the data of its files.
+pub(crate) type Block = (u64, Vec<u8>);
+
+#[derive(Default)]
+struct State {
+    /// Stored data of every block, `None` until a block has been written.
+    stored: Vec<Option<Content>>,
+    /// The block files are currently appended to, and their data so far.
+    open: Option<Block>,
+    /// Blocks of the previous archive already copied, by their old number.
+    copied: HashMap<u64, u64>,
+}
+
+/// Concatenates small files into blocks that are compressed as one, so that
+/// repeats between files are found. Blocks are numbered in the order they are started.
+pub(crate) struct Blocks {
+    block_size: Option<u64>,
+    state: Mutex<State>,
+}
+
+impl Blocks {
+    pub(crate) fn new(block_size: Option<u64>) -> Self {
+        Blocks { block_size, state: Mutex::new(State::default()) }
+    }
+
+    /// Whether a file of `size` bytes goes into a block rather than being stored on its own.
+    pub(crate) fn accepts(&self, size: u64) -> bool {
+        self.block_size.is_some_and(|block_size| size < block_size)
+    }
+
+    /// Appends the data of a file `accepts` to the open block, starting a new block if it
+    /// does not fit. Returns the block and where the file starts in it, and the block that
+    /// was closed to make room, if any.
+    pub(crate) fn append(&self, data: Vec<u8>) -> ((u64, u64), Option<Block>) {
+        let block_size = self.block_size.unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        let closed = match &state.open {
+            Some((_, open)) if (open.len() + data.len()) as u64 <= block_size => None,
+            _ => {
+                state.stored.push(None);
+                let block = state.stored.len() as u64 - 1;
+                state.open.replace((block, Vec::new()))
+            },
+        };
+        let (block, open) = state.open.as_mut().unwrap();
+        let start = open.len() as u64;
+        open.extend(data);
+        ((*block, start), closed)
+    }
+
+    /// Closes the open block and returns its data, once every file has been appended.
+    pub(crate) fn close(&self) -> Option<Block> {
+        self.state.lock().unwrap().open.take()
+    }
+
+    /// Records where the data of block `block` was stored.
+    pub(crate) fn store(&self, block: u64, content: Content) {
+        self.state.lock().unwrap().stored[block as usize] = Some(content);
+    }
+
+    /// Copies block `block` of `previous` into `writer` the first time it is asked for,
+    /// and returns its number in the new archive.
+    pub(crate) fn copy(&self, block: u64, previous: &ArchiveReader, writer: &ArchiveWriter) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(&copied) = state.copied.get(&block) {
+            return Ok(copied);
+        }
+        let Content::Stored { offset, length, codec } = previous.index().block(block)? else {
+            return Err(Error::Corrupt(format!("solid block {} is missing", block)));
+        };
+        let content = writer.store_raw(&previous.read_raw(*offset, *length)?, codec)?;
+        state.stored.push(Some(content));
+        let copied = state.stored.len() as u64 - 1;
+        state.copied.insert(block, copied);
+        Ok(copied)
+    }
+
+    /// Returns the stored data of every block.
+    pub(crate) fn finish(&self) -> Result<Vec<Content>> {
+        mem::take(&mut self.state.lock().unwrap().stored).into_iter()
+            .map(|content| content.ok_or_else(|| Error::InvalidParameter("a solid block was not written".to_string())))
+            .collect()
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::{Read, Write};
+
+use crate::{error::{Error, Result}, file_system::FileData, header::{self, Header}, huffman::HuffmanTree};
+
+pub const BLOCK_SIZE: usize = 1 << 20;
+
+fn read_block<R: Read>(input: &mut R) -> Result<Vec<u8>> {
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    input.take(BLOCK_SIZE as u64).read_to_end(&mut block)?;
+    Ok(block)
+}
+
+/// Compresses `input` into a sequence of length-prefixed frames, each holding one
+/// block encoded with the best codec for it. A zero length frame ends the stream.
+pub fn compress<R: Read, W: Write>(mut input: R, mut output: W) -> Result<()> {
+    Header::new(header::STREAM_MAGIC, 0).write(&mut output)?;
+    loop {
+        let block = read_block(&mut input)?;
+        if block.is_empty() {
+            break;
+        }
+        let frame = bincode::serialize(&FileData::encode(block, None)?)?;
+        output.write_all(&(frame.len() as u32).to_le_bytes())?;
+        output.write_all(&frame)?;
+    }
+    output.write_all(&0u32.to_le_bytes())?;
+    Ok(output.flush()?)
+}
+
+fn read_exact<R: Read>(input: &mut R, buffer: &mut [u8]) -> Result<()> {
+    input.read_exact(buffer).map_err(|error| match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::Corrupt("stream is truncated".to_string()),
+        _ => Error::Io(error),
+    })
+}
+
+pub fn decompress<R: Read, W: Write>(mut input: R, mut output: W) -> Result<()> {
+    Header::read(header::STREAM_MAGIC, &mut input)?;
+    let tree = HuffmanTree {
+        children: vec![],
+        character: None,
+    };
+    loop {
+        let mut length = [0; 4];
+        read_exact(&mut input, &mut length)?;
+        let length = u32::from_le_bytes(length) as usize;
+        if length == 0 {
+            break;
+        }
+        let mut frame = vec![0; length];
+        read_exact(&mut input, &mut frame)?;
+        let data: FileData = bincode::deserialize(&frame)?;
+        if let FileData::FixedHuffman { .. } = data {
+            return Err(Error::Corrupt("fixed huffman frames are not allowed in a stream".to_string()));
+        }
+        output.write_all(&data.decode(&tree)?)?;
+    }
+    Ok(output.flush()?)
+}
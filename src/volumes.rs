@@ -0,0 +1,177 @@
+use std::{fs::{self, File}, io::{self, BufWriter, Read, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use crate::error::{Error, PathContext, Result};
+
+/// `x.tmy` is split into `x.tmy.001`, `x.tmy.002`, ...
+pub fn volume_path(base: &Path, number: usize) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(format!(".{:03}", number));
+    PathBuf::from(path)
+}
+
+/// Returns the base path if `path` names a split archive, either through its
+/// first volume or through the base path of volumes that exist.
+pub fn split_base(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_some_and(|extension| extension == "001") {
+        return Some(path.with_extension(""));
+    }
+    (!path.exists() && volume_path(path, 1).exists()).then(|| path.to_path_buf())
+}
+
+/// Removes the volumes of `base`, stopping at the first one that does not exist.
+pub fn remove_volumes(base: &Path) -> Result<()> {
+    let mut number = 1;
+    while let Ok(metadata) = fs::symlink_metadata(volume_path(base, number)) {
+        if metadata.is_file() {
+            let path = volume_path(base, number);
+            fs::remove_file(&path).at(&path)?;
+        }
+        number += 1;
+    }
+    Ok(())
+}
+
+fn with_path(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+/// Marks the first volume of a split archive. It is followed by the number of volumes as a
+/// little endian u32, filled in once the last volume is written, so a reader can tell
+/// missing volumes from the end of the archive however many are left.
+const VOLUME_MAGIC: [u8; 4] = *b"TMYV";
+const VOLUME_HEADER_LEN: u64 = 8;
+
+/// Writes volumes of exactly `volume_size` bytes, the first one starting with the volume header.
+pub struct VolumeWriter {
+    base: PathBuf,
+    volume_size: u64,
+    number: usize,
+    current: BufWriter<File>,
+    written: u64,
+}
+
+impl VolumeWriter {
+    pub fn create(base: &Path, volume_size: u64) -> Result<Self> {
+        if volume_size <= VOLUME_HEADER_LEN {
+            return Err(Error::InvalidParameter(format!("volume size must be greater than {} bytes", VOLUME_HEADER_LEN)));
+        }
+        // Volumes left over from an earlier, longer archive would be read as part of this one.
+        remove_volumes(base)?;
+        let path = volume_path(base, 1);
+        let mut current = BufWriter::new(File::create(&path).at(&path)?);
+        current.write_all(&VOLUME_MAGIC).and_then(|()| current.write_all(&0u32.to_le_bytes())).at(&path)?;
+        Ok(VolumeWriter { base: base.to_path_buf(), volume_size, number: 1, current, written: VOLUME_HEADER_LEN })
+    }
+
+    fn next_volume(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        self.number += 1;
+        let path = volume_path(&self.base, self.number);
+        self.current = BufWriter::new(File::create(&path).map_err(|error| with_path(&path, error))?);
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Records the number of volumes written so far in the first one.
+    fn write_count(&self) -> io::Result<()> {
+        let path = volume_path(&self.base, 1);
+        let count = u32::try_from(self.number).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many volumes"))?;
+        let mut first = fs::OpenOptions::new().write(true).open(&path).map_err(|error| with_path(&path, error))?;
+        first.seek(SeekFrom::Start(VOLUME_MAGIC.len() as u64))?;
+        first.write_all(&count.to_le_bytes())
+    }
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let limit = buffer.len().min((self.volume_size - self.written) as usize);
+        let written = self.current.write(&buffer[..limit])?;
+        self.written += written as u64;
+        if self.written == self.volume_size {
+            self.next_volume()?;
+        }
+        Ok(written)
+    }
+
+    /// Also records the number of volumes, so the set is complete once the archive is flushed.
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        self.write_count()
+    }
+}
+
+/// Reads the volumes of a split archive as one continuous archive.
+pub struct VolumeReader {
+    volumes: Vec<File>,
+    volume_size: u64,
+    length: u64,
+    position: u64,
+}
+
+impl VolumeReader {
+    pub fn open(base: &Path) -> Result<Self> {
+        let open = |number: usize| {
+            let path = volume_path(base, number);
+            match File::open(&path) {
+                Ok(volume) => Ok((volume.metadata().at(&path)?.len(), volume)),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Err(Error::MissingVolume(path)),
+                Err(error) => Err(error).at(&path),
+            }
+        };
+        let (volume_size, mut first) = open(1)?;
+        let mut header = [0; VOLUME_HEADER_LEN as usize];
+        if first.read_exact(&mut header).is_err() || header[..4] != VOLUME_MAGIC {
+            return Err(Error::Corrupt(format!("{} is not the first volume of a split archive", volume_path(base, 1).display())));
+        }
+        let count = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if count == 0 {
+            return Err(Error::Corrupt("split archive was not completely written".to_string()));
+        }
+
+        let mut volumes = vec![first];
+        let mut length = volume_size - VOLUME_HEADER_LEN;
+        for number in 2..=count {
+            let (size, volume) = open(number)?;
+            if size > volume_size || (number < count && size != volume_size) {
+                return Err(Error::Corrupt(format!("{} is not the same size as the first volume", volume_path(base, number).display())));
+            }
+            length += size;
+            volumes.push(volume);
+        }
+        Ok(VolumeReader { volumes, volume_size, length, position: 0 })
+    }
+
+    /// The volume holding byte `position` of the archive, and where that byte is in it.
+    fn locate(&self, position: u64) -> (usize, u64) {
+        let position = position + VOLUME_HEADER_LEN;
+        ((position / self.volume_size) as usize, position % self.volume_size)
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.length || buffer.is_empty() {
+            return Ok(0);
+        }
+        let (volume, offset) = self.locate(self.position);
+        let available = (self.volume_size - offset).min(self.length - self.position);
+        let limit = buffer.len().min(available as usize);
+        let volume = &mut self.volumes[volume];
+        volume.seek(SeekFrom::Start(offset))?;
+        let read = volume.read(&mut buffer[..limit])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for VolumeReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the archive"))?;
+        Ok(self.position)
+    }
+}
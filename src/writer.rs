@@ -0,0 +1,90 @@
+use std::{io::Write, sync::Mutex};
+
+use crate::{crypto::{Cipher, Encryption, Purpose}, error::Result, file_system::{Archive, Content, FileData}, header::{self, Header}};
+
+struct Output {
+    output: Box<dyn Write + Send>,
+    written: u64,
+}
+
+/// Writes an archive front to back: file data is appended as soon as it is
+/// compressed and the index follows once every entry is known.
+pub struct ArchiveWriter {
+    output: Mutex<Output>,
+    cipher: Option<Cipher>,
+    encrypt_index: bool,
+}
+
+impl ArchiveWriter {
+    /// Starts an archive in `output`, encrypted with a new key if `encryption` is given.
+    pub fn new(mut output: Box<dyn Write + Send>, encryption: Option<&Encryption>) -> Result<Self> {
+        let flags = match encryption {
+            Some(Encryption { encrypt_index: true, .. }) => header::FLAG_ENCRYPTED | header::FLAG_ENCRYPTED_INDEX,
+            Some(_) => header::FLAG_ENCRYPTED,
+            None => 0,
+        };
+        Header::new(header::ARCHIVE_MAGIC, flags).write(&mut output)?;
+        let (cipher, written) = match encryption {
+            Some(encryption) => {
+                let (cipher, key_block) = Cipher::create(&encryption.passphrase)?;
+                output.write_all(&key_block)?;
+                (Some(cipher), key_block.len() as u64)
+            },
+            None => (None, 0),
+        };
+        Ok(ArchiveWriter {
+            output: Mutex::new(Output { output, written }),
+            cipher,
+            encrypt_index: encryption.is_some_and(|encryption| encryption.encrypt_index),
+        })
+    }
+
+    pub(crate) fn store(&self, data: &FileData) -> Result<Content> {
+        self.store_raw(&bincode::serialize(data)?, &data.codec())
+    }
+
+    /// Appends an already serialized `FileData`, such as one copied from another archive.
+    pub(crate) fn store_raw(&self, serialized: &[u8], codec: &str) -> Result<Content> {
+        let mut output = self.output.lock().unwrap();
+        let offset = output.written;
+        let encrypted;
+        let payload = match &self.cipher {
+            Some(cipher) => {
+                encrypted = cipher.encrypt(Purpose::Payload, offset, serialized)?;
+                &encrypted
+            },
+            None => serialized,
+        };
+        output.output.write_all(payload)?;
+        output.written += payload.len() as u64;
+        Ok(Content::Stored { offset, length: payload.len() as u64, codec: codec.to_string() })
+    }
+
+    /// Writes `index` and the offset it starts at, completing the archive.
+    pub fn finish(self, index: &Archive) -> Result<()> {
+        if !matches!(index, Archive::Root { .. }) {
+            return Err(Archive::not_root());
+        }
+        let Output { mut output, written } = self.output.into_inner().unwrap();
+        let index_offset = Header::LEN as u64 + written;
+        let serialized = match self.cipher {
+            Some(_) => {
+                let mut index = index.clone();
+                index.clear_checksums();
+                bincode::serialize(&index)?
+            },
+            None => bincode::serialize(index)?,
+        };
+        // A readable index is still followed by a tag, so changes to it are noticed.
+        match &self.cipher {
+            Some(cipher) if self.encrypt_index => output.write_all(&cipher.encrypt(Purpose::Index, index_offset, &serialized)?)?,
+            Some(cipher) => {
+                output.write_all(&serialized)?;
+                output.write_all(&cipher.authenticate(Purpose::Index, index_offset, &serialized)?)?;
+            },
+            None => output.write_all(&serialized)?,
+        }
+        output.write_all(&index_offset.to_le_bytes())?;
+        Ok(output.flush()?)
+    }
+}
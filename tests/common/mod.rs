@@ -0,0 +1,32 @@
+#![allow(dead_code)]
+
+use std::{fs, fs::File, path::Path};
+
+use huffman::{Archive, ArchiveReader, ArchiveWriter, CompressOptions, Encryption, NoProgress};
+
+/// Creates the files of `files`, as path and contents, below `root`.
+pub fn create_files(root: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// Archives `input` into `output`.
+pub fn compress(input: &Path, output: &Path, options: &CompressOptions, encryption: Option<&Encryption>) {
+    let writer = ArchiveWriter::new(Box::new(File::create(output).unwrap()), encryption).unwrap();
+    let archive = Archive::read_paths(&[input], options, &writer, &NoProgress).unwrap();
+    writer.finish(&archive).unwrap();
+}
+
+/// The paths of every entry of `archive`.
+pub fn paths(archive: &Archive) -> Vec<String> {
+    let mut paths = Vec::new();
+    archive.walk(&mut |path, _| paths.push(path.to_string()));
+    paths
+}
+
+pub fn open(archive: &Path) -> ArchiveReader {
+    ArchiveReader::open(archive, None).unwrap()
+}
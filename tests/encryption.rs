@@ -0,0 +1,104 @@
+mod common;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use huffman::{header::Header, Archive, ArchiveReader, CompressOptions, Content, Encryption, Error};
+
+const PASSPHRASE: &str = "correct horse";
+
+fn encryption(encrypt_index: bool) -> Encryption {
+    Encryption { passphrase: PASSPHRASE.to_string(), encrypt_index }
+}
+
+#[test]
+fn readable_index_has_no_checksums() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    common::create_files(&input, &[("a.txt", "some text"), ("dir/b.txt", "more text")]);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), Some(&encryption(false)));
+
+    let reader = ArchiveReader::open(&archive, Some(PASSPHRASE)).unwrap();
+    let mut files = 0;
+    reader.index().walk(&mut |path, entry| if let Archive::File { checksum, .. } = entry {
+        assert_eq!(*checksum, 0);
+        assert_eq!(reader.decode_entry(path, entry).unwrap(), fs::read(dir.path().join(path.to_string())).unwrap());
+        files += 1;
+    });
+    assert_eq!(files, 2);
+}
+
+/// Archives a small tree with `encryption` and returns the directory holding it and the archive path.
+fn encrypted_archive(encrypt_index: bool) -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    common::create_files(&input, &[("a.txt", "some text to encrypt"), ("dir/b.txt", "more text")]);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), Some(&encryption(encrypt_index)));
+    (dir, archive)
+}
+
+fn flip_byte(path: &Path, offset: u64) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes[offset as usize] ^= 1;
+    fs::write(path, bytes).unwrap();
+}
+
+fn index_offset(path: &Path) -> u64 {
+    let bytes = fs::read(path).unwrap();
+    u64::from_le_bytes(bytes[bytes.len() - 8..].try_into().unwrap())
+}
+
+#[test]
+fn wrong_password_is_refused() {
+    for encrypt_index in [false, true] {
+        let (_dir, archive) = encrypted_archive(encrypt_index);
+        assert!(matches!(ArchiveReader::open(&archive, Some("wrong")), Err(Error::WrongPassword)));
+    }
+}
+
+#[test]
+fn encrypted_index_needs_the_password() {
+    let (dir, archive) = encrypted_archive(true);
+    assert!(matches!(ArchiveReader::open(&archive, None), Err(Error::PasswordRequired)));
+
+    let reader = ArchiveReader::open(&archive, Some(PASSPHRASE)).unwrap();
+    assert_eq!(common::paths(reader.index()), ["data", "data/a.txt", "data/dir", "data/dir/b.txt"]);
+    reader.index().walk(&mut |path, entry| if let Archive::File { .. } = entry {
+        assert_eq!(reader.decode_entry(path, entry).unwrap(), fs::read(dir.path().join(path.to_string())).unwrap());
+    });
+}
+
+#[test]
+fn readable_index_hides_data_without_the_password() {
+    let (_dir, archive) = encrypted_archive(false);
+    let reader = ArchiveReader::open(&archive, None).unwrap();
+    assert_eq!(common::paths(reader.index()), ["data", "data/a.txt", "data/dir", "data/dir/b.txt"]);
+    let entry = reader.index().find(&"data/a.txt".into()).unwrap();
+    assert!(matches!(reader.decode_entry(&"data/a.txt".into(), entry), Err(Error::PasswordRequired)));
+}
+
+#[test]
+fn tampered_payload_is_detected() {
+    let (_dir, archive) = encrypted_archive(false);
+    let reader = ArchiveReader::open(&archive, Some(PASSPHRASE)).unwrap();
+    let Some(Archive::File { content: Content::Stored { offset, .. }, .. }) = reader.index().find(&"data/a.txt".into()) else {
+        panic!("data/a.txt is not stored");
+    };
+    let payload_start = Header::LEN as u64 + offset;
+    drop(reader);
+    flip_byte(&archive, payload_start + 4);
+
+    let reader = ArchiveReader::open(&archive, Some(PASSPHRASE)).unwrap();
+    let entry = reader.index().find(&"data/a.txt".into()).unwrap();
+    assert!(matches!(reader.decode_entry(&"data/a.txt".into(), entry), Err(Error::Corrupt(_))));
+}
+
+#[test]
+fn tampered_index_is_detected() {
+    for encrypt_index in [false, true] {
+        let (_dir, archive) = encrypted_archive(encrypt_index);
+        flip_byte(&archive, index_offset(&archive) + 2);
+        assert!(matches!(ArchiveReader::open(&archive, Some(PASSPHRASE)), Err(Error::Corrupt(_))));
+    }
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use std::{fs, os::unix::fs::symlink, path::Path, sync::Mutex, time::UNIX_EPOCH};
+
+use huffman::{metadata::Timestamp, Archive, ArchiveReader, CompressOptions, ExtractOptions, ExtractReport, Metadata, NoProgress, OverwritePolicy, Progress};
+
+const METADATA: Metadata = Metadata {
+    mode: 0o755,
+    modified: Timestamp { secs: 0, nanos: 0 },
+    accessed: Timestamp { secs: 0, nanos: 0 },
+    uid: 0,
+    gid: 0,
+};
+
+fn directory(name: &str, children: Vec<Archive>) -> Archive {
+    Archive::Directory { name: name.into(), metadata: METADATA, children }
+}
+
+fn symlink_entry(name: &str, target: &Path) -> Archive {
+    Archive::Symlink { name: name.into(), target: target.to_str().unwrap().into() }
+}
+
+/// A reader for any archive, the crafted entries below have no data to read from it.
+fn reader(dir: &Path) -> ArchiveReader {
+    let input = dir.join("empty");
+    fs::create_dir(&input).unwrap();
+    let archive = dir.join("empty.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+    common::open(&archive)
+}
+
+fn extract(dir: &Path, children: Vec<Archive>, output: &Path, overwrite: OverwritePolicy) -> ExtractReport {
+    let archive = Archive::Root { name: "crafted".into(), children, tree: Vec::new(), blocks: Vec::new() };
+    let options = ExtractOptions { overwrite, restore_ownership: false };
+    archive.write_directory(&reader(dir), output, &options, &NoProgress).unwrap()
+}
+
+/// Remembers the last update, as files and bytes done and their totals.
+#[derive(Default)]
+struct LastUpdate(Mutex<(u64, u64, u64, u64)>);
+
+impl Progress for LastUpdate {
+    fn update(&self, files: u64, bytes: u64, total_files: u64, total_bytes: u64) {
+        *self.0.lock().unwrap() = (files, bytes, total_files, total_bytes);
+    }
+}
+
+fn refused(report: &ExtractReport) -> Vec<(String, &str)> {
+    report.refused.iter().map(|(path, reason)| (path.to_string(), reason.as_str())).collect()
+}
+
+#[test]
+fn unsafe_names_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output");
+    let children = vec![
+        directory("..", vec![directory("escaped", vec![])]),
+        directory("/abs", vec![]),
+        directory("nul\0name", vec![]),
+        directory("safe", vec![directory("..", vec![])]),
+    ];
+    let report = extract(dir.path(), children, &output, OverwritePolicy::Skip);
+
+    assert_eq!(refused(&report), [
+        ("..".to_string(), "name refers to a directory outside the entry"),
+        ("/abs".to_string(), "name contains a path separator"),
+        ("nul\0name".to_string(), "name contains a NUL byte"),
+        ("safe/..".to_string(), "name refers to a directory outside the entry"),
+    ]);
+    assert!(!dir.path().join("escaped").exists());
+    assert_eq!(fs::read_dir(&output).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>(), ["safe"]);
+}
+
+#[test]
+fn directory_after_symlink_of_the_same_name_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = dir.path().join("outside");
+    fs::create_dir(&outside).unwrap();
+    let output = dir.path().join("output");
+    let children = vec![
+        symlink_entry("dup", &outside),
+        directory("dup", vec![directory("inner", vec![])]),
+    ];
+    let report = extract(dir.path(), children, &output, OverwritePolicy::Skip);
+
+    assert_eq!(refused(&report), [("dup".to_string(), "duplicate entry name")]);
+    assert!(fs::symlink_metadata(output.join("dup")).unwrap().is_symlink());
+    assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+}
+
+#[test]
+fn existing_symlinked_directory_is_never_followed() {
+    for overwrite in [OverwritePolicy::Skip, OverwritePolicy::Overwrite, OverwritePolicy::Rename] {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        symlink(&outside, output.join("link")).unwrap();
+        let children = vec![directory("link", vec![directory("inner", vec![])])];
+        extract(dir.path(), children, &output, overwrite);
+
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0, "{:?} wrote through the link", overwrite);
+    }
+}
+
+#[test]
+fn keep_newer_replaces_older_hard_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input");
+    common::create_files(&input, &[("original.txt", "archived")]);
+    fs::hard_link(input.join("original.txt"), input.join("link.txt")).unwrap();
+    let archive = dir.path().join("links.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+
+    let output = dir.path().join("output");
+    for name in ["original.txt", "link.txt"] {
+        common::create_files(&output.join("input"), &[(name, "stale")]);
+        fs::File::options().write(true).open(output.join("input").join(name)).unwrap().set_modified(UNIX_EPOCH).unwrap();
+    }
+    let reader = common::open(&archive);
+    let options = ExtractOptions { overwrite: OverwritePolicy::KeepNewer, restore_ownership: false };
+    reader.index().write_directory(&reader, &output, &options, &NoProgress).unwrap();
+
+    for name in ["original.txt", "link.txt"] {
+        assert_eq!(fs::read_to_string(output.join("input").join(name)).unwrap(), "archived", "{}", name);
+    }
+}
+
+#[test]
+fn hard_links_count_towards_progress() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input");
+    common::create_files(&input, &[("original.txt", "archived")]);
+    fs::hard_link(input.join("original.txt"), input.join("link.txt")).unwrap();
+    let archive = dir.path().join("links.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+
+    let reader = common::open(&archive);
+    let output = dir.path().join("output");
+    // The second time every entry already exists and is skipped.
+    for _ in 0..2 {
+        let progress = LastUpdate::default();
+        reader.index().write_directory(&reader, &output, &ExtractOptions::default(), &progress).unwrap();
+        let (files, bytes, total_files, total_bytes) = *progress.0.lock().unwrap();
+        assert_eq!((files, bytes), (total_files, total_bytes));
+        assert_eq!(total_files, 2);
+    }
+}
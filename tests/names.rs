@@ -0,0 +1,28 @@
+mod common;
+
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+use huffman::{CompressOptions, ExtractOptions, NoProgress};
+
+#[test]
+fn names_that_are_not_utf8_are_kept_byte_for_byte() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    fs::create_dir(&input).unwrap();
+    let name = OsStr::from_bytes(b"caf\xff.txt");
+    fs::write(input.join(name), "contents").unwrap();
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+
+    let reader = common::open(&archive);
+    // Listings replace what is not UTF-8.
+    assert_eq!(common::paths(reader.index()), ["data", "data/caf\u{fffd}.txt"]);
+
+    let output = dir.path().join("output");
+    reader.index().write_directory(&reader, &output, &ExtractOptions::default(), &NoProgress).unwrap();
+    let names = fs::read_dir(output.join("data")).unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, [name]);
+    assert_eq!(fs::read(output.join("data").join(name)).unwrap(), b"contents");
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use glob::Pattern;
+use huffman::{edit, CompressOptions, NoProgress};
+
+#[test]
+fn wildcards_do_not_match_across_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    common::create_files(&input, &[("a.txt", "a"), ("dir/b.txt", "b"), ("dir/sub/c.txt", "c")]);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+
+    let reader = common::open(&archive);
+    let selected = reader.index().select(&[Pattern::new("data/dir/*.txt").unwrap()]).unwrap();
+    assert_eq!(common::paths(&selected), ["data", "data/dir", "data/dir/b.txt"]);
+}
+
+#[test]
+fn delete_leaves_nested_entries_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    common::create_files(&input, &[("dir/b.ext", "b"), ("dir/keep.txt", "k"), ("dir/sub/c.ext", "c")]);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions::default(), None);
+
+    let removed = edit::delete(&archive, &[Pattern::new("data/dir/*.ext").unwrap()], None, &NoProgress).unwrap();
+    assert_eq!(removed.iter().map(ToString::to_string).collect::<Vec<_>>(), ["data/dir/b.ext"]);
+    let reader = common::open(&archive);
+    assert_eq!(common::paths(reader.index()), ["data", "data/dir", "data/dir/keep.txt", "data/dir/sub", "data/dir/sub/c.ext"]);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::{fs, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use huffman::{Archive, CompressOptions};
+
+/// Sets the access time of every entry below `path`, as reading the tree on another day would.
+fn set_access_times(path: &Path, accessed: SystemTime) {
+    fs::File::open(path).unwrap().set_times(fs::FileTimes::new().set_accessed(accessed)).unwrap();
+    if path.is_dir() {
+        fs::read_dir(path).unwrap().for_each(|entry| set_access_times(&entry.unwrap().path(), accessed));
+    }
+}
+
+fn compress_with_threads(threads: usize, input: &Path, output: &Path, options: &CompressOptions) -> Vec<u8> {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap()
+        .install(|| common::compress(input, output, options, None));
+    fs::read(output).unwrap()
+}
+
+fn create_tree(root: &Path) -> std::path::PathBuf {
+    let input = root.join("data");
+    let large = (0..3000).map(|number| format!("{}\n", number)).collect::<String>();
+    common::create_files(&input, &[("b.txt", "bbb"), ("a.txt", "aaa"), ("dir/large.txt", &large), ("dir/copy.txt", "aaa"), ("dir/sub/c.txt", "ccc")]);
+    fs::hard_link(input.join("b.txt"), input.join("dir/link.txt")).unwrap();
+    input
+}
+
+#[test]
+fn archiving_again_gives_the_same_bytes() {
+    for options in [CompressOptions::default(), CompressOptions { solid_block_size: Some(4096), ..CompressOptions::default() }] {
+        let dir = tempfile::tempdir().unwrap();
+        let input = create_tree(dir.path());
+
+        let first = compress_with_threads(4, &input, &dir.path().join("first.tmy"), &options);
+        let second = compress_with_threads(1, &input, &dir.path().join("second.tmy"), &options);
+        // Two days later, after something else read the tree.
+        set_access_times(&input, SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60));
+        let third = compress_with_threads(8, &input, &dir.path().join("third.tmy"), &options);
+        set_access_times(&input, UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let fourth = compress_with_threads(2, &input, &dir.path().join("fourth.tmy"), &options);
+        assert!(first == second && second == third && third == fourth, "{:?}", options);
+    }
+}
+
+#[test]
+fn access_times_are_recorded_when_asked_for() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = create_tree(dir.path());
+    let accessed = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    set_access_times(&input, accessed);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions { access_times: true, ..CompressOptions::default() }, None);
+
+    let reader = common::open(&archive);
+    let Some(Archive::File { metadata, .. }) = reader.index().find(&"data/dir/large.txt".into()) else {
+        panic!("data/dir/large.txt is missing");
+    };
+    assert_eq!(SystemTime::from(metadata.accessed), accessed);
+}
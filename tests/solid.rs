@@ -0,0 +1,38 @@
+mod common;
+
+use std::fs;
+
+use glob::Pattern;
+use huffman::{edit, Archive, CompressOptions, NoProgress};
+
+/// Bytes that do not compress, so whether they are still stored shows in the archive size.
+fn noise(len: usize) -> String {
+    let mut state = 0x2545f491u32;
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        char::from(b'a' + (state % 26) as u8)
+    }).collect()
+}
+
+#[test]
+fn delete_compresses_blocks_that_lost_a_file_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    let noise = noise(64 * 1024);
+    common::create_files(&input, &[("a.txt", "kept in the block\n"), ("b.txt", "kept in the block\n"), ("c.txt", "also kept\n"), ("noise.txt", &noise)]);
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions { solid_block_size: Some(1 << 20), ..CompressOptions::default() }, None);
+    assert!(fs::metadata(&archive).unwrap().len() > 32 * 1024);
+
+    // b.txt only refers to a.txt, so it needs the data of the block as well.
+    edit::delete(&archive, &[Pattern::new("data/a.txt").unwrap(), Pattern::new("data/noise.txt").unwrap()], None, &NoProgress).unwrap();
+    assert!(fs::metadata(&archive).unwrap().len() < 4 * 1024);
+    let reader = common::open(&archive);
+    assert_eq!(common::paths(reader.index()), ["data", "data/b.txt", "data/c.txt"]);
+    reader.index().walk(&mut |path, entry| if let Archive::File { .. } = entry {
+        let expected = fs::read(input.join(path.to_string().trim_start_matches("data/"))).unwrap();
+        assert_eq!(reader.decode_entry(path, entry).unwrap(), expected, "{}", path);
+    });
+}
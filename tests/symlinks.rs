@@ -0,0 +1,21 @@
+mod common;
+
+use std::os::unix::fs::symlink;
+
+use huffman::{Archive, CompressOptions};
+
+#[test]
+fn following_links_keeps_dangling_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("data");
+    common::create_files(&input, &[("file.txt", "contents")]);
+    symlink("missing.txt", input.join("dangling")).unwrap();
+    symlink("file.txt", input.join("link")).unwrap();
+    let archive = dir.path().join("data.tmy");
+    common::compress(&input, &archive, &CompressOptions { follow_symlinks: true, ..CompressOptions::default() }, None);
+
+    let reader = common::open(&archive);
+    let index = reader.index();
+    assert!(matches!(index.find(&"data/dangling".into()), Some(Archive::Symlink { target, .. }) if target.to_string() == "missing.txt"));
+    assert!(matches!(index.find(&"data/link".into()), Some(Archive::File { .. })));
+}
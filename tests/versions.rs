@@ -0,0 +1,132 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use huffman::{edit, header::{self, Header}, stream, Archive, ArchiveReader, CompressOptions, ExtractOptions, NoProgress};
+
+fn header(magic: [u8; 4], version: u16) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    Header { magic, version, flags: 0 }.write(&mut bytes).unwrap();
+    bytes
+}
+
+fn fixture(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// What the fixtures were made from, `data` below, by the build that wrote each format.
+fn expected_contents(name: &str) -> Vec<u8> {
+    match name {
+        "hello.txt" | "same.txt" => "hello world\n".repeat(50).into_bytes(),
+        "numbers.txt" | "hard.txt" => (0..2000).map(|number| format!("{}\n", number)).collect::<String>().into_bytes(),
+        "short.txt" => b"the quick brown fox jumps over the lazy dog\n".to_vec(),
+        "empty" | "extra.txt" => Vec::new(),
+        _ => panic!("unexpected file {}", name),
+    }
+}
+
+fn expected_paths(version: u16) -> Vec<&'static str> {
+    let mut paths = vec!["data", "data/dir", "data/dir/empty", "data/dir/numbers.txt", "data/hello.txt"];
+    if version == 0 {
+        // The first release failed unless some file used the shared tree.
+        paths.push("data/dir/short.txt");
+    }
+    if version >= 4 {
+        paths.push("data/link");
+    }
+    if version >= 5 {
+        paths.extend(["data/hard.txt", "data/same.txt"]);
+    }
+    paths.sort();
+    paths
+}
+
+fn check(reader: &ArchiveReader, version: u16, added: &[&str]) {
+    let mut paths = common::paths(reader.index());
+    paths.sort();
+    let mut expected = expected_paths(version);
+    expected.extend(added);
+    expected.sort();
+    assert_eq!(paths, expected, "format {}", version);
+    reader.index().walk(&mut |path, entry| match entry {
+        Archive::File { name, .. } => assert_eq!(reader.decode_entry(path, entry).unwrap(), expected_contents(&name.to_string()), "format {}: {}", version, path),
+        Archive::Symlink { target, .. } => assert_eq!(target.to_string(), "hello.txt"),
+        _ => (),
+    });
+}
+
+#[test]
+fn older_archives_are_read() {
+    for version in 0..header::ARCHIVE_VERSION {
+        let reader = ArchiveReader::open(&fixture(&format!("v{}.tmy", version)), None).unwrap();
+        check(&reader, version, &[]);
+
+        let dir = tempfile::tempdir().unwrap();
+        reader.index().write_directory(&reader, dir.path(), &ExtractOptions::default(), &NoProgress).unwrap();
+        for path in expected_paths(version) {
+            let path = dir.path().join(path);
+            let metadata = fs::symlink_metadata(&path).unwrap();
+            if metadata.is_file() {
+                assert_eq!(fs::read(&path).unwrap(), expected_contents(path.file_name().unwrap().to_str().unwrap()), "format {}: {}", version, path.display());
+            }
+        }
+    }
+}
+
+#[test]
+fn older_archives_are_upgraded_when_edited() {
+    for version in 0..header::ARCHIVE_VERSION {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("data.tmy");
+        fs::copy(fixture(&format!("v{}.tmy", version)), &archive).unwrap();
+        common::create_files(dir.path(), &[("extra.txt", "")]);
+        edit::add(&archive, &[dir.path().join("extra.txt")], &CompressOptions::default(), None, &NoProgress).unwrap();
+
+        assert_eq!(fs::read(&archive).unwrap()[4..6], header::ARCHIVE_VERSION.to_le_bytes());
+        check(&common::open(&archive), version, &["extra.txt"]);
+    }
+}
+
+#[test]
+fn first_stream_format_is_read() {
+    let mut output = Vec::new();
+    stream::decompress(&fs::read(fixture("v1.stream")).unwrap()[..], &mut output).unwrap();
+    assert_eq!(output, expected_contents("numbers.txt"));
+}
+
+#[test]
+fn streams_round_trip() {
+    for input in [expected_contents("numbers.txt"), expected_contents("empty")] {
+        let mut compressed = Vec::new();
+        stream::compress(&input[..], &mut compressed).unwrap();
+        assert_eq!(compressed[..4], header::STREAM_MAGIC);
+        assert_eq!(compressed[4..6], header::STREAM_VERSION.to_le_bytes());
+
+        let mut output = Vec::new();
+        stream::decompress(&compressed[..], &mut output).unwrap();
+        assert_eq!(output, input);
+    }
+}
+
+#[test]
+fn newer_versions_name_what_they_are() {
+    let error = stream::decompress(&header(header::STREAM_MAGIC, header::STREAM_VERSION + 1)[..], Vec::new()).unwrap_err();
+    assert_eq!(error.to_string(), format!("stream from newer version: format {} is not supported, this build reads up to {}", header::STREAM_VERSION + 1, header::STREAM_VERSION));
+
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("newer.tmy");
+    fs::write(&archive, header(header::ARCHIVE_MAGIC, header::ARCHIVE_VERSION + 1)).unwrap();
+    let error = ArchiveReader::open(&archive, None).err().unwrap();
+    assert_eq!(error.to_string(), format!("archive from newer version: format {} is not supported, this build reads up to {}", header::ARCHIVE_VERSION + 1, header::ARCHIVE_VERSION));
+}
+
+#[test]
+fn other_files_are_not_archives() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, contents) in [("short", &b"tm"[..]), ("text", &b"hello world\n"[..])] {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        let error = ArchiveReader::open(&path, None).err().unwrap();
+        assert_eq!(error.to_string(), "corrupt data: not a tmy archive", "{}", name);
+    }
+}
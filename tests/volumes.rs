@@ -0,0 +1,74 @@
+mod common;
+
+use std::{fs, path::{Path, PathBuf}};
+
+use huffman::{volumes::{self, VolumeWriter}, Archive, ArchiveReader, ArchiveWriter, CompressOptions, Error, NoProgress};
+
+/// Bytes that do not compress, so the archive spreads over several volumes.
+fn noise(length: usize) -> String {
+    let mut state = 0x2545_f491_u32;
+    (0..length).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        char::from(b'a' + (state % 26) as u8)
+    }).collect()
+}
+
+/// Splits an archive of a few small files into volumes of 500 bytes and returns their base path and count.
+fn split_archive(dir: &Path) -> (PathBuf, usize) {
+    let input = dir.join("data");
+    common::create_files(&input, &[("a.txt", &noise(1000)), ("b.txt", &noise(700))]);
+    let base = dir.join("data.tmy");
+    let writer = ArchiveWriter::new(Box::new(VolumeWriter::create(&base, 500).unwrap()), None).unwrap();
+    let archive = Archive::read_paths(&[&input], &CompressOptions::default(), &writer, &NoProgress).unwrap();
+    writer.finish(&archive).unwrap();
+    let count = (1..).take_while(|&number| volumes::volume_path(&base, number).exists()).count();
+    (base, count)
+}
+
+fn missing_volume(base: &Path) -> PathBuf {
+    match ArchiveReader::open(&volumes::volume_path(base, 1), None) {
+        Err(Error::MissingVolume(path)) => path,
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("archive opened with a volume missing"),
+    }
+}
+
+#[test]
+fn volumes_are_read_as_one_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, count) = split_archive(dir.path());
+    assert!(count > 3);
+
+    let reader = ArchiveReader::open(&volumes::volume_path(&base, 1), None).unwrap();
+    reader.index().walk(&mut |path, entry| if let Archive::File { .. } = entry {
+        assert_eq!(reader.decode_entry(path, entry).unwrap(), fs::read(dir.path().join(path.to_string())).unwrap());
+    });
+}
+
+#[test]
+fn missing_middle_volume_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, _) = split_archive(dir.path());
+    fs::remove_file(volumes::volume_path(&base, 2)).unwrap();
+    assert_eq!(missing_volume(&base), volumes::volume_path(&base, 2));
+}
+
+#[test]
+fn missing_last_volume_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, count) = split_archive(dir.path());
+    fs::remove_file(volumes::volume_path(&base, count)).unwrap();
+    assert_eq!(missing_volume(&base), volumes::volume_path(&base, count));
+}
+
+#[test]
+fn lone_first_volume_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let (base, count) = split_archive(dir.path());
+    for number in 2..=count {
+        fs::remove_file(volumes::volume_path(&base, number)).unwrap();
+    }
+    assert_eq!(missing_volume(&base), volumes::volume_path(&base, 2));
+}